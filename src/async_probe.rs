@@ -0,0 +1,178 @@
+use crate::format::{Format, MAX_FTYP_LEN};
+use crate::probe::{LimitError, ProbeOptions};
+use crate::{Error, Image, ImageType, Result};
+use std::io::{BufRead, Read, Seek, SeekFrom};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+/// Smallest range fetched at a time. The first fetch also covers format detection, which
+/// needs up to `MAX_FTYP_LEN` bytes.
+const INITIAL_READ: usize = 4 * 1024;
+const _: () = assert!(INITIAL_READ >= MAX_FTYP_LEN);
+
+/// Byte ranges fetched so far, as `(offset, bytes)` relative to the start of the image.
+type Segments = Vec<(u64, Vec<u8>)>;
+
+impl Image {
+    /// Async counterpart of `Image::probe_reader`.
+    ///
+    /// The synchronous parsers run over the byte ranges fetched so far; whenever a parser
+    /// reads from a range that has not been fetched yet, `reader` is seeked there, the range
+    /// is fetched and parsing restarts. Parts of the file the parser skips are never read.
+    pub async fn probe_async<R: AsyncRead + AsyncSeek + Unpin>(
+        reader: &mut R,
+        options: &ProbeOptions,
+    ) -> Result<Image> {
+        let start = reader.stream_position().await?;
+        let len = reader.seek(SeekFrom::End(0)).await?.saturating_sub(start);
+        let end = options.max_bytes.map_or(len, |limit| limit.min(len));
+
+        let mut segments = Segments::new();
+        fetch(reader, start, &mut segments, 0, INITIAL_READ, end).await?;
+        let magic = segments.first().map_or(&[][..], |(_, data)| data);
+        let format = Format::detect(magic).ok_or(Error::UnsupportedFormat)?;
+
+        let r#type = loop {
+            let mut window = Window::new(&segments, len, options.max_bytes);
+            let result = ImageType::probe_with(format, &mut window, options);
+            // Parsers may recover from a short read, e.g. by trying another chunk type, so
+            // any read of missing bytes means the result cannot be trusted yet.
+            match window.missing {
+                Some((offset, wanted)) => {
+                    fetch(reader, start, &mut segments, offset, wanted, end).await?;
+                }
+                None => break result?,
+            }
+        };
+
+        let mut crc32 = 0;
+        if options.crc32 {
+            reader.seek(SeekFrom::Start(start)).await?;
+            let mut chunk = vec![0u8; 64 * 1024];
+            loop {
+                let n = reader.read(&mut chunk).await?;
+                if n == 0 {
+                    break;
+                }
+                crc32 = const_crc32::crc32_seed(&chunk[..n], crc32);
+            }
+        }
+        Ok(Self::new(&r#type, crc32))
+    }
+}
+
+/// Reads at least `INITIAL_READ` bytes at `offset` into a new segment, stopping at `end` or
+/// at the next segment already fetched.
+async fn fetch<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    start: u64,
+    segments: &mut Segments,
+    offset: u64,
+    len: usize,
+    end: u64,
+) -> Result<()> {
+    let next = segments
+        .iter()
+        .map(|(segment, _)| *segment)
+        .filter(|segment| *segment > offset)
+        .min()
+        .unwrap_or(end);
+    let stop = offset
+        .saturating_add(len.max(INITIAL_READ) as u64)
+        .min(next)
+        .min(end);
+    if stop <= offset {
+        return Ok(());
+    }
+    reader.seek(SeekFrom::Start(start + offset)).await?;
+    let mut data = Vec::with_capacity((stop - offset) as usize);
+    (&mut *reader).take(stop - offset).read_to_end(&mut data).await?;
+    if data.is_empty() {
+        // The stream is shorter than it was when probing started.
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    segments.push((offset, data));
+    Ok(())
+}
+
+/// Sparse in-memory view of the image that records the first read of bytes not fetched yet.
+/// Reads past `limit` fail like they do through `LimitReader`.
+struct Window<'a> {
+    segments: &'a [(u64, Vec<u8>)],
+    pos: u64,
+    len: u64,
+    limit: Option<u64>,
+    missing: Option<(u64, usize)>,
+}
+
+impl<'a> Window<'a> {
+    fn new(segments: &'a [(u64, Vec<u8>)], len: u64, limit: Option<u64>) -> Self {
+        Self {
+            segments,
+            pos: 0,
+            len,
+            limit,
+            missing: None,
+        }
+    }
+
+    /// Fetched bytes from the current position on; empty at the end of the stream, or when
+    /// they are yet to be fetched.
+    fn available(&mut self, wanted: usize) -> std::io::Result<&'a [u8]> {
+        if let Some(limit) = self.limit {
+            if self.pos >= limit {
+                return Err(std::io::Error::other(LimitError(limit)));
+            }
+        }
+        if self.pos >= self.len {
+            return Ok(&[]);
+        }
+        let segments = self.segments;
+        for (offset, data) in segments {
+            if (*offset..*offset + data.len() as u64).contains(&self.pos) {
+                return Ok(&data[(self.pos - offset) as usize..]);
+            }
+        }
+        self.missing.get_or_insert((self.pos, wanted));
+        Ok(&[])
+    }
+}
+
+impl Read for Window<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = self.available(buf.len())?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl BufRead for Window<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.available(1)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl Seek for Window<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
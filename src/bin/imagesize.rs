@@ -0,0 +1,277 @@
+use imagesize::length::Length;
+use imagesize::probe::ProbeOptions;
+use imagesize::scan::{scan, ScanEntry, ScanOptions};
+use imagesize::Image;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: imagesize [OPTIONS] <PATH>...
+
+Prints the dimensions, resolution and physical size of images.
+Directories are searched recursively for files that look like images.
+
+Options:
+  -o, --output <table|json|csv>         Output mode [default: table]
+  -u, --unit <in|cm|mm|pt|twip|emu>     Unit for the physical size [default: in]
+      --probe                           Only read headers (crc32 is reported as 0)
+  -h, --help                            Print this help";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+struct Args {
+    output: Output,
+    unit: &'static str,
+    probe: bool,
+    paths: Vec<PathBuf>,
+}
+
+struct Row {
+    path: String,
+    image: Image,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("imagesize: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let probe = args.probe.then(ProbeOptions::default);
+    let mut results = Vec::new();
+    for path in &args.paths {
+        if path.is_dir() {
+            let options = ScanOptions {
+                probe: probe.clone(),
+                ..Default::default()
+            };
+            let mut entries: Vec<ScanEntry> = scan(path, &options).collect();
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            results.extend(entries.into_iter().map(|entry| (entry.path, entry.result)));
+        } else {
+            let result = match &probe {
+                Some(options) => Image::probe_file(&path.to_string_lossy(), options),
+                None => Image::from_file(&path.to_string_lossy()),
+            };
+            results.push((path.clone(), result));
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut failed = false;
+    for (path, result) in results {
+        let path = path.to_string_lossy().into_owned();
+        match result {
+            Ok(image) => rows.push(Row { path, image }),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                failed = true;
+            }
+        }
+    }
+
+    match args.output {
+        Output::Table => print_table(&rows, args.unit),
+        Output::Json => print_json(&rows, args.unit),
+        Output::Csv => print_csv(&rows, args.unit),
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse_args(mut iter: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = Args {
+        output: Output::Table,
+        unit: "in",
+        probe: false,
+        paths: Vec::new(),
+    };
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-o" | "--output" => {
+                args.output = match iter.next().as_deref() {
+                    Some("table") => Output::Table,
+                    Some("json") => Output::Json,
+                    Some("csv") => Output::Csv,
+                    other => return Err(format!("invalid output mode: {:?}", other)),
+                }
+            }
+            "-u" | "--unit" => {
+                args.unit = match iter.next().as_deref() {
+                    Some("in") => "in",
+                    Some("cm") => "cm",
+                    Some("mm") => "mm",
+                    Some("pt") => "pt",
+                    Some("twip") => "twip",
+                    Some("emu") => "emu",
+                    other => return Err(format!("invalid unit: {:?}", other)),
+                }
+            }
+            "--probe" => args.probe = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option: {}", flag))
+            }
+            path => args.paths.push(PathBuf::from(path)),
+        }
+    }
+    if args.paths.is_empty() {
+        return Err("no paths given".to_string());
+    }
+    Ok(args)
+}
+
+fn physical(length: &Length, unit: &str) -> f64 {
+    match unit {
+        "cm" => length.to_cm(),
+        "mm" => length.to_mm(),
+        "pt" => length.to_pt(),
+        "twip" => length.to_twips(),
+        "emu" => length.to_emu(),
+        _ => length.to_inches(),
+    }
+}
+
+fn fields(row: &Row, unit: &str) -> [String; 9] {
+    let image = &row.image;
+    [
+        row.path.clone(),
+        image.dimensions.0.to_string(),
+        image.dimensions.1.to_string(),
+        image.x_dpi.to_string(),
+        image.y_dpi.to_string(),
+        text_number(physical(&image.width, unit)),
+        text_number(physical(&image.height, unit)),
+        image.content_type.clone(),
+        format!("{:08x}", image.crc32),
+    ]
+}
+
+/// Two decimals, or an empty field when the size is unknown because the resolution is 0.
+fn text_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:.2}", value)
+    } else {
+        String::new()
+    }
+}
+
+fn headers(unit: &str) -> [String; 9] {
+    [
+        "path".to_string(),
+        "width_px".to_string(),
+        "height_px".to_string(),
+        "x_dpi".to_string(),
+        "y_dpi".to_string(),
+        format!("width_{}", unit),
+        format!("height_{}", unit),
+        "content_type".to_string(),
+        "crc32".to_string(),
+    ]
+}
+
+fn print_table(rows: &[Row], unit: &str) {
+    let mut lines = vec![headers(unit)];
+    lines.extend(rows.iter().map(|row| {
+        fields(row, unit).map(|field| if field.is_empty() { "-".to_string() } else { field })
+    }));
+    let mut widths = [0; 9];
+    for line in &lines {
+        for (width, field) in widths.iter_mut().zip(line) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (field, width))| match i {
+                // Left-align text columns, right-align numbers.
+                0 | 7 => format!("{:<width$}", field),
+                _ => format!("{:>width$}", field),
+            })
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+fn print_csv(rows: &[Row], unit: &str) {
+    let quote = |field: &String| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.clone()
+        }
+    };
+    println!("{}", headers(unit).iter().map(quote).collect::<Vec<_>>().join(","));
+    for row in rows {
+        println!("{}", fields(row, unit).iter().map(quote).collect::<Vec<_>>().join(","));
+    }
+}
+
+fn print_json(rows: &[Row], unit: &str) {
+    let objects: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let image = &row.image;
+            format!(
+                "{{\"path\":{},\"width_px\":{},\"height_px\":{},\"x_dpi\":{},\"y_dpi\":{},\
+                 \"width\":{},\"height\":{},\"unit\":\"{}\",\"content_type\":{},\"crc32\":\"{:08x}\"}}",
+                json_string(&row.path),
+                image.dimensions.0,
+                image.dimensions.1,
+                image.x_dpi,
+                image.y_dpi,
+                json_number(physical(&image.width, unit)),
+                json_number(physical(&image.height, unit)),
+                unit,
+                json_string(&image.content_type),
+                image.crc32,
+            )
+        })
+        .collect();
+    println!("[{}]", objects.join(","));
+}
+
+/// Four decimals, or `null` when the size is unknown because the resolution is 0.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:.4}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
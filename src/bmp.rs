@@ -1,13 +1,15 @@
-use crate::length::Length;
+use crate::error::Context;
+use crate::format::Format;
 use crate::ImageReader;
-use crate::Result;
+use crate::{Error, Result};
 use binrw::BinRead;
 use std::io::SeekFrom;
-use std::io::{BufRead, Read, Seek};
+use std::io::{BufRead, Seek};
 
 const PPM_FACTOR: f32 = 0.0254;
 
 #[derive(BinRead, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[br(little)]
 #[br(magic(b"BM"))]
 pub struct Bmp {
@@ -22,7 +24,7 @@ pub struct Bmp {
 
 impl Bmp {
     pub fn new<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
-        Ok(Bmp::read(reader)?)
+        Bmp::read(reader).context(Format::Bmp, reader)
     }
 }
 
@@ -46,3 +48,32 @@ fn _dpi(ppm: u32) -> u32 {
     }
     (ppm as f32 * PPM_FACTOR).round() as u32
 }
+
+/// Returns a copy of `data` with the `x_ppm`/`y_ppm` header fields set to `x_dpi` by `y_dpi`.
+pub fn set_dpi(data: &[u8], x_dpi: u32, y_dpi: u32) -> Result<Vec<u8>> {
+    let reader = &mut std::io::Cursor::new(data);
+    Bmp::read(reader).context(Format::Bmp, reader)?;
+    // BITMAPCOREHEADER (12 bytes) has no resolution fields.
+    let header_size = u32::from_le_bytes(data[0x0E..0x12].try_into().unwrap());
+    if header_size < 40 {
+        return Err(Error::CorruptChunk {
+            format: Format::Bmp,
+            offset: 0x0E,
+            reason: format!("{}-byte header has no resolution fields", header_size),
+        });
+    }
+    let ppm = |dpi: u32| {
+        let ppm = (dpi as f32 / PPM_FACTOR).round();
+        if dpi == 0 || ppm > i32::MAX as f32 {
+            return Err(Error::InvalidResolution {
+                format: Format::Bmp,
+                dpi,
+            });
+        }
+        Ok(ppm as u32)
+    };
+    let mut out = data.to_vec();
+    out[0x26..0x2A].copy_from_slice(&ppm(x_dpi)?.to_le_bytes());
+    out[0x2A..0x2E].copy_from_slice(&ppm(y_dpi)?.to_le_bytes());
+    Ok(out)
+}
@@ -0,0 +1,139 @@
+use crate::format::Format;
+use crate::length::Length;
+use crate::probe::LimitError;
+use std::io::Seek;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    IOError(#[source] std::io::Error),
+    #[error("unsupported image format")]
+    UnsupportedFormat,
+    #[error("{format}: unexpected end of input at byte {offset}")]
+    Truncated { format: Format, offset: u64 },
+    #[error("{format}: invalid magic bytes at byte {offset}")]
+    InvalidMagic { format: Format, offset: u64 },
+    #[error("{format}: corrupt chunk or segment at byte {offset}: {reason}")]
+    CorruptChunk {
+        format: Format,
+        offset: u64,
+        reason: String,
+    },
+    #[error("{format}: dimension {value} at byte {offset} does not fit in 32 bits")]
+    DimensionOverflow {
+        format: Format,
+        offset: u64,
+        value: u64,
+    },
+    #[error("read limit of {limit} bytes exceeded")]
+    LimitExceeded { limit: u64 },
+    #[error("{format}: CRC mismatch in {chunk} chunk at byte {offset}")]
+    ChecksumMismatch {
+        format: Format,
+        chunk: String,
+        offset: u64,
+    },
+    #[error("{format}: a resolution of {dpi} dpi cannot be stored")]
+    InvalidResolution { format: Format, dpi: u32 },
+    #[error("{0} is not a valid DrawingML extent or offset")]
+    InvalidExtent(Length),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<LimitError>())
+        {
+            Some(LimitError(limit)) => Error::LimitExceeded { limit: *limit },
+            None => Error::IOError(err),
+        }
+    }
+}
+
+impl Error {
+    /// Classifies a binrw failure. `position` is used when binrw does not report one.
+    pub(crate) fn from_binrw(format: Format, err: binrw::Error, position: u64) -> Self {
+        if let Some(limit) = _limit(&err) {
+            return Error::LimitExceeded { limit };
+        }
+        if _is_eof(&err) {
+            return Error::Truncated {
+                format,
+                offset: _pos(&err).unwrap_or(position),
+            };
+        }
+        match err {
+            binrw::Error::Backtrace(backtrace) => {
+                Self::from_binrw(format, *backtrace.error, position)
+            }
+            binrw::Error::BadMagic { pos, .. } | binrw::Error::NoVariantMatch { pos } => {
+                Error::InvalidMagic {
+                    format,
+                    offset: pos,
+                }
+            }
+            binrw::Error::Io(err) => err.into(),
+            err => Error::CorruptChunk {
+                format,
+                offset: _pos(&err).unwrap_or(position),
+                reason: err.to_string(),
+            },
+        }
+    }
+}
+
+fn _pos(err: &binrw::Error) -> Option<u64> {
+    match err {
+        binrw::Error::BadMagic { pos, .. }
+        | binrw::Error::AssertFail { pos, .. }
+        | binrw::Error::Custom { pos, .. }
+        | binrw::Error::NoVariantMatch { pos }
+        | binrw::Error::EnumErrors { pos, .. } => Some(*pos),
+        binrw::Error::Backtrace(backtrace) => _pos(&backtrace.error),
+        _ => None,
+    }
+}
+
+/// Like `binrw::Error::is_eof`, but an enum counts as truncated as soon as one of its
+/// variants ran out of input, since the catch-all variants accept any tag.
+fn _is_eof(err: &binrw::Error) -> bool {
+    match err {
+        binrw::Error::EnumErrors { variant_errors, .. } => {
+            variant_errors.iter().any(|(_, err)| _is_eof(err))
+        }
+        binrw::Error::Backtrace(backtrace) => _is_eof(&backtrace.error),
+        err => err.is_eof(),
+    }
+}
+
+fn _limit(err: &binrw::Error) -> Option<u64> {
+    match err {
+        binrw::Error::Io(err) => err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<LimitError>())
+            .map(|LimitError(limit)| *limit),
+        binrw::Error::EnumErrors { variant_errors, .. } => {
+            variant_errors.iter().find_map(|(_, err)| _limit(err))
+        }
+        binrw::Error::Backtrace(backtrace) => _limit(&backtrace.error),
+        _ => None,
+    }
+}
+
+/// Attaches the format and the reader position to binrw failures.
+pub(crate) trait Context<T> {
+    fn context<R: Seek>(self, format: Format, reader: &mut R) -> Result<T>;
+}
+
+impl<T> Context<T> for binrw::BinResult<T> {
+    fn context<R: Seek>(self, format: Format, reader: &mut R) -> Result<T> {
+        self.map_err(|err| {
+            let position = reader.stream_position().unwrap_or_default();
+            Error::from_binrw(format, err, position)
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use crate::length::Length;
+use crate::Image;
+
+/// How `Image::fit` scales an image into a box. Every mode preserves the aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FitMode {
+    /// As large as possible while fitting inside the box.
+    #[default]
+    Contain,
+    /// As small as possible while covering the box; one side may overflow.
+    Cover,
+    /// Matches the box width; the height may overflow.
+    Width,
+    /// Matches the box height; the width may overflow.
+    Height,
+    /// Like `Contain`, but never larger than the natural size.
+    ScaleDown,
+}
+
+impl Image {
+    /// Natural physical size as displayed, with width and height swapped for the
+    /// transposing orientations 5-8. A size that is unknown because the resolution is 0,
+    /// as for a JPEG with neither JFIF nor EXIF data, is taken at 72 dpi.
+    pub fn display_size(&self) -> (Length, Length) {
+        let natural = |length: Length, px: u32| {
+            if length.to_emu().is_finite() {
+                length
+            } else {
+                Length::Inches(px as f64 / 72.0)
+            }
+        };
+        let width = natural(self.width, self.dimensions.0);
+        let height = natural(self.height, self.dimensions.1);
+        if (5..=8).contains(&self.orientation) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Scales the displayed image into a `max_width` by `max_height` box. The returned
+    /// width is in the unit of `max_width` and the height in the unit of `max_height`.
+    pub fn fit(&self, max_width: Length, max_height: Length, mode: FitMode) -> (Length, Length) {
+        let (width, height) = self.display_size();
+        if width.to_emu() <= 0.0 || height.to_emu() <= 0.0 {
+            return (width.convert(max_width), height.convert(max_height));
+        }
+        let (x, y) = (max_width / width, max_height / height);
+        let scale = match mode {
+            FitMode::Contain => x.min(y),
+            FitMode::Cover => x.max(y),
+            FitMode::Width => x,
+            FitMode::Height => y,
+            FitMode::ScaleDown => x.min(y).min(1.0),
+        };
+        ((width * scale).convert(max_width), (height * scale).convert(max_height))
+    }
+}
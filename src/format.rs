@@ -0,0 +1,172 @@
+/// Image container formats the crate knows how to parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Format {
+    Bmp,
+    Gif,
+    Jpeg,
+    Png,
+    Tiff,
+    Webp,
+    Avif,
+    Heic,
+    Heif,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::Bmp => "BMP",
+            Format::Gif => "GIF",
+            Format::Jpeg => "JPEG",
+            Format::Png => "PNG",
+            Format::Tiff => "TIFF",
+            Format::Webp => "WebP",
+            Format::Avif => "AVIF",
+            Format::Heic => "HEIC",
+            Format::Heif => "HEIF",
+        })
+    }
+}
+
+/// Number of leading bytes to read before calling `Format::detect`. ISO-BMFF files may
+/// list their brands further in; see `Format::sniff_len`.
+pub const SNIFF_LEN: usize = 32;
+
+/// Largest `ftyp` box read for format detection.
+pub const MAX_FTYP_LEN: usize = 1024;
+
+impl Format {
+    pub const ALL: [Format; 9] = [
+        Format::Bmp,
+        Format::Gif,
+        Format::Jpeg,
+        Format::Png,
+        Format::Tiff,
+        Format::Webp,
+        Format::Avif,
+        Format::Heic,
+        Format::Heif,
+    ];
+
+    /// Number of leading bytes `detect` needs, given the first `SNIFF_LEN` bytes of a file:
+    /// the whole `ftyp` box (up to `MAX_FTYP_LEN`) for ISO-BMFF files, `SNIFF_LEN` otherwise.
+    pub fn sniff_len(data: &[u8]) -> usize {
+        if data.get(4..8) == Some(b"ftyp") {
+            let size = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+            size.clamp(SNIFF_LEN, MAX_FTYP_LEN)
+        } else {
+            SNIFF_LEN
+        }
+    }
+
+    /// Identifies the format from the leading bytes of the file.
+    pub fn detect(data: &[u8]) -> Option<Format> {
+        if data.starts_with(b"\x89PNG\x0D\x0A\x1A\x0A") {
+            Some(Format::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if data.starts_with(b"II\x2A\x00")
+            || data.starts_with(b"MM\x00\x2A")
+            || data.starts_with(b"II\x2B\x00")
+            || data.starts_with(b"MM\x00\x2B")
+        {
+            Some(Format::Tiff)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Some(Format::Webp)
+        } else if data.get(4..8) == Some(b"ftyp") && data.len() >= 12 {
+            let major = data[8..12].try_into().unwrap();
+            let size = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+            let compatible: Vec<[u8; 4]> = data
+                .get(16..size.min(data.len()))
+                .unwrap_or_default()
+                .chunks_exact(4)
+                .map(|brand| brand.try_into().unwrap())
+                .collect();
+            Format::from_brands(&major, &compatible)
+        } else if data.starts_with(b"BM") && _is_dib_header_size(data.get(0x0E..0x12)) {
+            Some(Format::Bmp)
+        } else {
+            None
+        }
+    }
+
+    /// Classifies an ISO-BMFF `ftyp` box by its brands.
+    pub fn from_brands(major: &[u8; 4], compatible: &[[u8; 4]]) -> Option<Format> {
+        let has = |brands: &[&[u8; 4]]| {
+            brands.contains(&major) || compatible.iter().any(|brand| brands.contains(&brand))
+        };
+        if has(&[b"avif", b"avis"]) {
+            Some(Format::Avif)
+        } else if has(&[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx"]) {
+            Some(Format::Heic)
+        } else if has(&[b"mif1", b"msf1"]) {
+            Some(Format::Heif)
+        } else {
+            None
+        }
+    }
+
+    /// Maps a file extension (without the dot, any case) to a format.
+    pub fn from_ext(ext: &str) -> Option<Format> {
+        match ext.to_lowercase().as_str() {
+            "bmp" | "dib" => Some(Format::Bmp),
+            "gif" => Some(Format::Gif),
+            "jpeg" | "jpg" | "jpe" | "jfif" => Some(Format::Jpeg),
+            "png" => Some(Format::Png),
+            "tiff" | "tif" => Some(Format::Tiff),
+            "webp" => Some(Format::Webp),
+            "avif" | "avifs" => Some(Format::Avif),
+            "heic" | "heics" => Some(Format::Heic),
+            "heif" | "heifs" | "hif" => Some(Format::Heif),
+            _ => None,
+        }
+    }
+
+    /// Maps the extension of `path` to a format, if it has a known one.
+    pub fn from_path(path: &str) -> Option<Format> {
+        std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_ext)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Bmp => "image/bmp",
+            Format::Gif => "image/gif",
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::Tiff => "image/tiff",
+            Format::Webp => "image/webp",
+            Format::Avif => "image/avif",
+            Format::Heic => "image/heic",
+            Format::Heif => "image/heif",
+        }
+    }
+
+    pub fn default_ext(&self) -> &'static str {
+        match self {
+            Format::Bmp => "bmp",
+            Format::Gif => "gif",
+            Format::Jpeg => "jpeg",
+            Format::Png => "png",
+            Format::Tiff => "tiff",
+            Format::Webp => "webp",
+            Format::Avif => "avif",
+            Format::Heic => "heic",
+            Format::Heif => "heif",
+        }
+    }
+}
+
+/// Whether the little-endian size at 0x0E of a BMP file is that of a known DIB header,
+/// from the 12-byte OS/2 `BITMAPCOREHEADER` to the 124-byte `BITMAPV5HEADER`.
+fn _is_dib_header_size(size: Option<&[u8]>) -> bool {
+    size.and_then(|size| size.try_into().ok())
+        .map(u32::from_le_bytes)
+        .is_some_and(|size| matches!(size, 12 | 40 | 52 | 56 | 64 | 108 | 124))
+}
@@ -1,17 +1,20 @@
-use crate::length::Length;
+use crate::error::Context;
+use crate::format::Format;
 use crate::{ImageReader, Result};
 use binrw::BinRead;
 use std::io::{BufRead, Seek};
 
 #[derive(Debug, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[br(little)]
 #[br(magic(b"GIF"))]
 pub struct Gif {
-    r#type: Type,
+    pub r#type: Type,
     pub width: u16,
     pub height: u16,
 }
 #[derive(Debug, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[br(little)]
 pub enum Type {
     #[br(magic(b"87a"))]
@@ -22,7 +25,7 @@ pub enum Type {
 
 impl Gif {
     pub fn new<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
-        Ok(Gif::read(reader)?)
+        Gif::read(reader).context(Format::Gif, reader)
     }
 }
 
@@ -0,0 +1,276 @@
+use crate::error::Context;
+use crate::format::Format;
+use crate::{Error, ImageReader, Result};
+use binrw::BinRead;
+use std::io::{BufRead, Seek, SeekFrom};
+
+/// HEIF container (including HEIC and AVIF), described by the properties of its primary item.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heif {
+    pub major_brand: [u8; 4],
+    pub compatible_brands: Vec<[u8; 4]>,
+    pub primary_item: u32,
+    /// Stored size of the primary item, from `ispe`.
+    pub width: u32,
+    pub height: u32,
+    /// Anti-clockwise rotation in degrees, from `irot`.
+    pub rotation: u16,
+    pub mirror: Option<Mirror>,
+    /// Bits per channel, from `pixi`.
+    pub bits_per_channel: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mirror {
+    /// Mirrored about a vertical axis (left and right swapped).
+    Vertical,
+    /// Mirrored about a horizontal axis (top and bottom swapped).
+    Horizontal,
+}
+
+#[derive(BinRead, Debug)]
+#[br(big)]
+pub struct BoxHeader {
+    pub size: u32,
+    pub box_type: [u8; 4],
+    #[br(if(size == 1))]
+    pub large_size: u64,
+}
+
+impl BoxHeader {
+    /// Reads a box header and returns it with the offset of the end of the box.
+    fn next<R: BufRead + Seek>(reader: &mut R) -> Result<(BoxHeader, u64)> {
+        let start = reader.stream_position()?;
+        let header: BoxHeader = read(reader)?;
+        let end = match header.size {
+            0 => Some(reader.seek(SeekFrom::End(0))?),
+            1 => start.checked_add(header.large_size),
+            size => Some(start + size as u64),
+        };
+        // A box is at least as large as its own header.
+        let header_len = if header.size == 1 { 16 } else { 8 };
+        let Some(end) = end.filter(|end| *end >= start + header_len) else {
+            return Err(Error::CorruptChunk {
+                format: Format::Heif,
+                offset: start,
+                reason: format!(
+                    "invalid size for box {}",
+                    String::from_utf8_lossy(&header.box_type)
+                ),
+            });
+        };
+        Ok((header, end))
+    }
+}
+
+#[derive(BinRead, Debug)]
+#[br(big)]
+struct FullBox {
+    version: u8,
+    flags: [u8; 3],
+}
+
+#[derive(Debug)]
+enum Property {
+    Ispe(u32, u32),
+    Irot(u8),
+    Imir(u8),
+    Pixi(Vec<u8>),
+    Other,
+}
+
+/// Reads a big-endian value, reporting failures against the HEIF container.
+fn read<T, R>(reader: &mut R) -> Result<T>
+where
+    T: BinRead,
+    for<'a> T::Args<'a>: Default,
+    R: BufRead + Seek,
+{
+    T::read_be_args(reader, Default::default()).context(Format::Heif, reader)
+}
+
+impl Heif {
+    pub fn new<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
+        let mut heif = Heif::default();
+        let mut properties = Vec::new();
+        let mut associations = Vec::new();
+
+        let (header, end) = BoxHeader::next(reader)?;
+        if &header.box_type != b"ftyp" {
+            return Err(Error::InvalidMagic {
+                format: Format::Heif,
+                offset: 0,
+            });
+        }
+        heif.major_brand = read(reader)?;
+        reader.seek(SeekFrom::Current(4))?;
+        while reader.stream_position()? + 4 <= end {
+            heif.compatible_brands.push(read(reader)?);
+        }
+        reader.seek(SeekFrom::Start(end))?;
+
+        loop {
+            let (header, end) = BoxHeader::next(reader)?;
+            if &header.box_type == b"meta" {
+                read::<FullBox, _>(reader)?;
+                heif.read_meta(reader, end, &mut properties, &mut associations)?;
+                break;
+            }
+            reader.seek(SeekFrom::Start(end))?;
+        }
+
+        let primary = associations
+            .iter()
+            .find(|(item, _)| *item == heif.primary_item)
+            .or(associations.first())
+            .map(|(_, indices): &(u32, Vec<u16>)| indices.clone())
+            .unwrap_or_else(|| (1..=properties.len() as u16).collect());
+        for index in primary {
+            match properties.get(index as usize - 1) {
+                Some(Property::Ispe(width, height)) => {
+                    heif.width = *width;
+                    heif.height = *height;
+                }
+                Some(Property::Irot(angle)) => heif.rotation = (*angle as u16 & 3) * 90,
+                Some(Property::Imir(axis)) => {
+                    heif.mirror = Some(if axis & 1 == 0 {
+                        Mirror::Vertical
+                    } else {
+                        Mirror::Horizontal
+                    })
+                }
+                Some(Property::Pixi(bits)) => heif.bits_per_channel = bits.clone(),
+                _ => {}
+            }
+        }
+
+        Ok(heif)
+    }
+
+    fn read_meta<R: BufRead + Seek>(
+        &mut self,
+        reader: &mut R,
+        meta_end: u64,
+        properties: &mut Vec<Property>,
+        associations: &mut Vec<(u32, Vec<u16>)>,
+    ) -> Result<()> {
+        while reader.stream_position()? < meta_end {
+            let (header, end) = BoxHeader::next(reader)?;
+            match &header.box_type {
+                b"pitm" => {
+                    let full: FullBox = read(reader)?;
+                    self.primary_item = if full.version == 0 {
+                        read::<u16, _>(reader)? as u32
+                    } else {
+                        read::<u32, _>(reader)?
+                    };
+                }
+                b"iprp" => {
+                    while reader.stream_position()? < end {
+                        let (header, end) = BoxHeader::next(reader)?;
+                        match &header.box_type {
+                            b"ipco" => {
+                                while reader.stream_position()? < end {
+                                    properties.push(Self::read_property(reader)?);
+                                }
+                            }
+                            b"ipma" => associations.extend(Self::read_associations(reader)?),
+                            _ => {}
+                        }
+                        reader.seek(SeekFrom::Start(end))?;
+                    }
+                }
+                _ => {}
+            }
+            reader.seek(SeekFrom::Start(end))?;
+        }
+        Ok(())
+    }
+
+    fn read_property<R: BufRead + Seek>(reader: &mut R) -> Result<Property> {
+        let (header, end) = BoxHeader::next(reader)?;
+        let property = match &header.box_type {
+            b"ispe" => {
+                read::<FullBox, _>(reader)?;
+                Property::Ispe(read(reader)?, read(reader)?)
+            }
+            b"irot" => Property::Irot(read(reader)?),
+            b"imir" => Property::Imir(read(reader)?),
+            b"pixi" => {
+                read::<FullBox, _>(reader)?;
+                let count: u8 = read(reader)?;
+                let mut bits = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    bits.push(read(reader)?);
+                }
+                Property::Pixi(bits)
+            }
+            _ => Property::Other,
+        };
+        reader.seek(SeekFrom::Start(end))?;
+        Ok(property)
+    }
+
+    fn read_associations<R: BufRead + Seek>(reader: &mut R) -> Result<Vec<(u32, Vec<u16>)>> {
+        let full: FullBox = read(reader)?;
+        let entry_count: u32 = read(reader)?;
+        let mut associations = Vec::new();
+        for _ in 0..entry_count {
+            let item = if full.version == 0 {
+                read::<u16, _>(reader)? as u32
+            } else {
+                read::<u32, _>(reader)?
+            };
+            let count: u8 = read(reader)?;
+            let mut indices = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                // The top bit flags the property as essential.
+                let index = if full.flags[2] & 1 == 1 {
+                    read::<u16, _>(reader)? & 0x7FFF
+                } else {
+                    (read::<u8, _>(reader)? & 0x7F) as u16
+                };
+                if index != 0 {
+                    indices.push(index);
+                }
+            }
+            associations.push((item, indices));
+        }
+        Ok(associations)
+    }
+
+    pub fn format(&self) -> Format {
+        Format::from_brands(&self.major_brand, &self.compatible_brands).unwrap_or(Format::Heif)
+    }
+}
+
+impl ImageReader for Heif {
+    fn dimension(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// `irot` and `imir` as an EXIF orientation. The rotation is applied before the mirror.
+    fn orientation(&self) -> u16 {
+        match (self.rotation, self.mirror) {
+            (0, None) => 1,
+            (0, Some(Mirror::Vertical)) | (180, Some(Mirror::Horizontal)) => 2,
+            (180, None) => 3,
+            (0, Some(Mirror::Horizontal)) | (180, Some(Mirror::Vertical)) => 4,
+            (90, Some(Mirror::Horizontal)) | (270, Some(Mirror::Vertical)) => 5,
+            (270, None) => 6,
+            (90, Some(Mirror::Vertical)) | (270, Some(Mirror::Horizontal)) => 7,
+            (90, None) => 8,
+            _ => 1,
+        }
+    }
+
+    fn x_dpi(&self) -> u32 {
+        72
+    }
+
+    fn y_dpi(&self) -> u32 {
+        72
+    }
+}
@@ -1,11 +1,14 @@
-use crate::tiff::{IfdEntry, Tiff};
+use crate::error::Context;
+use crate::format::Format;
+use crate::tiff::Tiff;
 use crate::ImageReader;
-use binrw::helpers::{until, until_eof};
-use binrw::{BinRead, BinReaderExt};
+use binrw::helpers::until;
+use binrw::BinRead;
 use std::cmp::PartialEq;
-use std::io::{BufRead, Cursor, Seek};
+use std::io::Cursor;
 
 #[derive(BinRead, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[br(big)]
 pub struct Jpeg {
     #[br(ignore)]
@@ -16,7 +19,11 @@ pub struct Jpeg {
     pub x_dpi: u32,
     #[br(ignore)]
     pub y_dpi: u32,
+    /// EXIF orientation (1-8), 1 when there is no EXIF data.
+    #[br(calc = 1)]
+    pub orientation: u16,
     #[br(parse_with = until(|seg: &Segment| seg.is_sos()))]
+    #[cfg_attr(feature = "serde", serde(skip))]
     segments: Vec<Segment>,
 }
 
@@ -39,10 +46,7 @@ pub enum Segment {
 
 impl Segment {
     pub fn is_sos(&self) -> bool {
-        match self {
-            Segment::SOS(_) => true,
-            _ => false,
-        }
+        matches!(self, Segment::SOS(_))
     }
 }
 
@@ -140,7 +144,7 @@ pub struct App1 {
 impl App1 {
     pub fn tiff(&self) -> crate::Result<Tiff> {
         let mut cursor = Cursor::new(&self.data);
-        Ok(Tiff::new(&mut cursor)?)
+        Tiff::new(&mut cursor)
     }
 }
 
@@ -289,23 +293,58 @@ pub enum Marker {
 
 impl Jpeg {
     pub fn new<R: std::io::BufRead + std::io::Seek>(reader: &mut R) -> crate::Result<Self> {
-        let mut jpeg = Jpeg::read(reader)?;
+        let mut jpeg = Jpeg::read(reader).context(Format::Jpeg, reader)?;
+        jpeg.load()?;
+        Ok(jpeg)
+    }
+
+    /// Reads segments only up to the first SOF, which follows the APP0/APP1 resolution
+    /// segments in well-formed files.
+    pub fn probe<R: std::io::BufRead + std::io::Seek>(reader: &mut R) -> crate::Result<Self> {
+        let mut segments = Vec::new();
+        loop {
+            let seg = Segment::read(reader).context(Format::Jpeg, reader)?;
+            let done = matches!(seg, Segment::SOF(_) | Segment::SOS(_));
+            segments.push(seg);
+            if done {
+                break;
+            }
+        }
 
-        for seg in &jpeg.segments {
+        let mut jpeg = Jpeg {
+            width: 0,
+            height: 0,
+            x_dpi: 0,
+            y_dpi: 0,
+            orientation: 1,
+            segments,
+        };
+        jpeg.load()?;
+        Ok(jpeg)
+    }
+
+    fn load(&mut self) -> crate::Result<()> {
+        for seg in &self.segments {
             if let Segment::App0(app0) = seg {
-                jpeg.x_dpi = app0.x_dpi();
-                jpeg.y_dpi = app0.y_dpi();
+                self.x_dpi = app0.x_dpi();
+                self.y_dpi = app0.y_dpi();
             } else if let Segment::App1(app1) = seg {
-                let tiff = app1.tiff()?;
-                jpeg.x_dpi = tiff.x_dpi();
-                jpeg.y_dpi = tiff.x_dpi();
+                // Damaged EXIF data leaves the JFIF resolution in place.
+                let Ok(tiff) = app1.tiff() else {
+                    continue;
+                };
+                if tiff.has_resolution() {
+                    self.x_dpi = tiff.x_dpi();
+                    self.y_dpi = tiff.y_dpi();
+                }
+                self.orientation = tiff.orientation;
             } else if let Segment::SOF(sof) = seg {
-                jpeg.width = sof.width as u32;
-                jpeg.height = sof.height as u32;
+                self.width = sof.width as u32;
+                self.height = sof.height as u32;
             }
         }
 
-        Ok(jpeg)
+        Ok(())
     }
 }
 
@@ -321,4 +360,73 @@ impl crate::ImageReader for Jpeg {
     fn y_dpi(&self) -> u32 {
         self.y_dpi
     }
+
+    fn orientation(&self) -> u16 {
+        self.orientation
+    }
+}
+
+/// Returns a copy of `data` with the JFIF density set to `x_dpi` by `y_dpi` dots per inch,
+/// inserting a JFIF APP0 segment after SOI when there is none. EXIF resolution tags, which
+/// take precedence when reading, are updated as well when present.
+pub fn set_dpi(data: &[u8], x_dpi: u32, y_dpi: u32) -> crate::Result<Vec<u8>> {
+    let density = |dpi: u32| match u16::try_from(dpi) {
+        Ok(density) if density > 0 => Ok(density.to_be_bytes()),
+        _ => Err(crate::Error::InvalidResolution {
+            format: Format::Jpeg,
+            dpi,
+        }),
+    };
+    let (x_density, y_density) = (density(x_dpi)?, density(y_dpi)?);
+
+    let reader = &mut Cursor::new(data);
+    let mut jfif = None;
+    let mut exif = None;
+    loop {
+        let start = reader.position() as usize;
+        let seg = Segment::read(reader).context(Format::Jpeg, reader)?;
+        let end = reader.position() as usize;
+        match seg {
+            Segment::App0(app0) if jfif.is_none() && &app0.identifier == b"JFIF\0" => {
+                jfif = Some(start)
+            }
+            Segment::App1(app1) if exif.is_none() => exif = Some((start, end, app1)),
+            Segment::SOS(_) | Segment::EOI => break,
+            _ => {}
+        }
+    }
+
+    let mut out = data.to_vec();
+    if let Some(start) = jfif {
+        // Marker (2), length (2), identifier (5) and version (2) precede the units.
+        out[start + 11] = 1;
+        out[start + 12..start + 14].copy_from_slice(&x_density);
+        out[start + 14..start + 16].copy_from_slice(&y_density);
+    }
+    if let Some((start, end, app1)) = exif {
+        if app1.tiff()?.has_resolution() {
+            let tiff = crate::tiff::set_dpi(&app1.data, x_dpi, y_dpi)?;
+            let length = u16::try_from(tiff.len() + 8).map_err(|_| crate::Error::CorruptChunk {
+                format: Format::Jpeg,
+                offset: start as u64,
+                reason: "EXIF segment too large".to_string(),
+            })?;
+            let mut segment = vec![0xFF, 0xE1];
+            segment.extend_from_slice(&length.to_be_bytes());
+            segment.extend_from_slice(b"Exif\0\0");
+            segment.extend_from_slice(&tiff);
+            out.splice(start..end, segment);
+        }
+    }
+    if jfif.is_none() {
+        let mut app0 = vec![0xFF, 0xE0, 0x00, 0x10];
+        app0.extend_from_slice(b"JFIF\0");
+        app0.extend_from_slice(&[1, 2, 1]);
+        app0.extend_from_slice(&x_density);
+        app0.extend_from_slice(&y_density);
+        app0.extend_from_slice(&[0, 0]);
+        // The first segment is SOI.
+        out.splice(2..2, app0);
+    }
+    Ok(out)
 }
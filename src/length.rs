@@ -1,34 +1,348 @@
-pub const _EMUS_PER_INCH: f32 = 914400f32;
-pub const _EMUS_PER_CM: f32 = 360000f32;
-pub const _EMUS_PER_MM: f32 = 36000f32;
-pub const _EMUS_PER_PT: f32 = 12700f32;
-pub const _EMUS_PER_TWIP: f32 = 635f32;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+use thiserror::Error;
 
-#[derive(Debug, Clone)]
+pub const _EMUS_PER_INCH: f64 = 914400f64;
+pub const _EMUS_PER_CM: f64 = 360000f64;
+pub const _EMUS_PER_MM: f64 = 36000f64;
+pub const _EMUS_PER_PT: f64 = 12700f64;
+pub const _EMUS_PER_TWIP: f64 = 635f64;
+pub const _EMUS_PER_PC: f64 = 152400f64;
+
+/// Resolution of a CSS `px`.
+pub const CSS_DPI: f64 = 96f64;
+/// Font size assumed for `em` when none is given, the CSS default of 16px.
+pub const DEFAULT_FONT_PT: f64 = 12f64;
+
+/// A physical length. Values of different units compare and combine through their size
+/// in EMU; arithmetic keeps the unit of the left-hand side.
+///
+/// Serialized as `{"unit": "inches", "value": 1.5}` with the `serde` feature.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "unit", content = "value", rename_all = "lowercase")
+)]
 pub enum Length {
-    Emu(f32),
-    Inches(f32),
-    Cm(f32),
-    Mm(f32),
-    Pt(f32),
-    Twips(f32),
+    Emu(f64),
+    Inches(f64),
+    Cm(f64),
+    Mm(f64),
+    Pt(f64),
+    Twips(f64),
+    /// Picas, 12 points each.
+    Pc(f64),
+    /// Pixels at `dpi` pixels per inch.
+    Px { value: f64, dpi: f64 },
+    /// Multiples of a font size given in points.
+    Em { value: f64, font_pt: f64 },
+}
+
+/// How a fractional value is turned into a whole number of units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Towards zero.
+    #[default]
+    Truncate,
+    /// Half-way cases away from zero.
+    Nearest,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            Rounding::Truncate => value.trunc(),
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+        }
+    }
 }
 
 impl Default for Length {
     fn default() -> Self {
-        Length::Emu(0f32)
+        Length::Emu(0f64)
     }
 }
 
 impl Length {
+    /// Size in EMU, truncated to a whole number.
     pub fn value(&self) -> u32 {
+        self.to_emu() as u32
+    }
+
+    /// The number stored in the variant, in its own unit.
+    pub fn amount(&self) -> f64 {
+        match *self {
+            Length::Emu(v)
+            | Length::Inches(v)
+            | Length::Cm(v)
+            | Length::Mm(v)
+            | Length::Pt(v)
+            | Length::Twips(v)
+            | Length::Pc(v)
+            | Length::Px { value: v, .. }
+            | Length::Em { value: v, .. } => v,
+        }
+    }
+
+    /// EMU in one unit of this variant.
+    fn emus_per_unit(&self) -> f64 {
+        match self {
+            Length::Emu(_) => 1f64,
+            Length::Inches(_) => _EMUS_PER_INCH,
+            Length::Cm(_) => _EMUS_PER_CM,
+            Length::Mm(_) => _EMUS_PER_MM,
+            Length::Pt(_) => _EMUS_PER_PT,
+            Length::Twips(_) => _EMUS_PER_TWIP,
+            Length::Pc(_) => _EMUS_PER_PC,
+            Length::Px { dpi, .. } => _EMUS_PER_INCH / dpi,
+            Length::Em { font_pt, .. } => font_pt * _EMUS_PER_PT,
+        }
+    }
+
+    /// Same variant with `amount` replaced.
+    fn with_amount(&self, amount: f64) -> Length {
         match self {
-            Length::Inches(inches) => (*inches * _EMUS_PER_INCH) as u32,
-            Length::Cm(cm) => (*cm * _EMUS_PER_CM) as u32,
-            Length::Emu(emu) => *emu as u32,
-            Length::Mm(mm) => (*mm * _EMUS_PER_MM) as u32,
-            Length::Pt(pt) => (*pt * _EMUS_PER_PT) as u32,
-            Length::Twips(twips) => (*twips * _EMUS_PER_TWIP) as u32,
+            Length::Emu(_) => Length::Emu(amount),
+            Length::Inches(_) => Length::Inches(amount),
+            Length::Cm(_) => Length::Cm(amount),
+            Length::Mm(_) => Length::Mm(amount),
+            Length::Pt(_) => Length::Pt(amount),
+            Length::Twips(_) => Length::Twips(amount),
+            Length::Pc(_) => Length::Pc(amount),
+            Length::Px { dpi, .. } => Length::Px { value: amount, dpi: *dpi },
+            Length::Em { font_pt, .. } => Length::Em {
+                value: amount,
+                font_pt: *font_pt,
+            },
+        }
+    }
+
+    pub fn to_emu(&self) -> f64 {
+        self.amount() * self.emus_per_unit()
+    }
+
+    pub fn to_inches(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_INCH
+    }
+
+    pub fn to_cm(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_CM
+    }
+
+    pub fn to_mm(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_MM
+    }
+
+    pub fn to_pt(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_PT
+    }
+
+    pub fn to_twips(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_TWIP
+    }
+
+    pub fn to_pc(&self) -> f64 {
+        self.to_emu() / _EMUS_PER_PC
+    }
+
+    /// Number of pixels at `dpi` pixels per inch.
+    pub fn to_px(&self, dpi: f64) -> f64 {
+        self.to_inches() * dpi
+    }
+
+    pub fn from_px(value: f64, dpi: f64) -> Length {
+        Length::Px { value, dpi }
+    }
+
+    /// CSS pixels, at 96 per inch.
+    pub fn css_px(value: f64) -> Length {
+        Length::Px {
+            value,
+            dpi: CSS_DPI,
+        }
+    }
+
+    /// Re-expresses this length in the unit of `unit`, whose own amount is ignored.
+    pub fn convert(&self, unit: Length) -> Length {
+        unit.with_amount(self.to_emu() / unit.emus_per_unit())
+    }
+
+    /// Rounds to a whole number of units of this variant.
+    pub fn round(&self, rounding: Rounding) -> Length {
+        self.with_amount(rounding.apply(self.amount()))
+    }
+
+    /// Whole EMU, for DrawingML extents.
+    pub fn emu(&self, rounding: Rounding) -> i64 {
+        rounding.apply(self.to_emu()) as i64
+    }
+
+    /// Whole twips, for WordprocessingML sizes.
+    pub fn twips(&self, rounding: Rounding) -> i64 {
+        rounding.apply(self.to_twips()) as i64
+    }
+}
+
+impl Add for Length {
+    type Output = Length;
+
+    fn add(self, rhs: Length) -> Length {
+        self.with_amount(self.amount() + rhs.to_emu() / self.emus_per_unit())
+    }
+}
+
+impl Sub for Length {
+    type Output = Length;
+
+    fn sub(self, rhs: Length) -> Length {
+        self.with_amount(self.amount() - rhs.to_emu() / self.emus_per_unit())
+    }
+}
+
+impl Mul<f64> for Length {
+    type Output = Length;
+
+    fn mul(self, rhs: f64) -> Length {
+        self.with_amount(self.amount() * rhs)
+    }
+}
+
+impl Div<f64> for Length {
+    type Output = Length;
+
+    fn div(self, rhs: f64) -> Length {
+        self.with_amount(self.amount() / rhs)
+    }
+}
+
+/// Ratio between two lengths.
+impl Div for Length {
+    type Output = f64;
+
+    fn div(self, rhs: Length) -> f64 {
+        self.to_emu() / rhs.to_emu()
+    }
+}
+
+impl Neg for Length {
+    type Output = Length;
+
+    fn neg(self) -> Length {
+        self.with_amount(-self.amount())
+    }
+}
+
+impl PartialEq for Length {
+    fn eq(&self, other: &Length) -> bool {
+        self.to_emu() == other.to_emu()
+    }
+}
+
+impl PartialOrd for Length {
+    fn partial_cmp(&self, other: &Length) -> Option<Ordering> {
+        self.to_emu().partial_cmp(&other.to_emu())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseLengthError {
+    #[error("invalid number in length {0:?}")]
+    InvalidNumber(String),
+    #[error("missing unit in length {0:?}")]
+    MissingUnit(String),
+    #[error("unknown unit {0:?}, expected one of emu, in, cm, mm, pt, tw, pc, px or em")]
+    UnknownUnit(String),
+    #[error("invalid resolution {0:?}, expected e.g. px@96dpi")]
+    InvalidDpi(String),
+    #[error("invalid font size {0:?}, expected e.g. em@12pt")]
+    InvalidFontSize(String),
+}
+
+/// Parses a number followed by a unit: `emu`, `in`, `cm`, `mm`, `pt`, `tw` (or `twip`), `pc`,
+/// `px` (CSS pixels) or `px@<dpi>dpi`, and `em` (of a 12pt font) or `em@<size>pt`.
+/// Whitespace between the number and the unit is allowed.
+impl FromStr for Length {
+    type Err = ParseLengthError;
+
+    fn from_str(s: &str) -> Result<Length, ParseLengthError> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| c.is_ascii_alphabetic() || c == '@')
+            .ok_or_else(|| ParseLengthError::MissingUnit(s.to_string()))?;
+        let (number, unit) = s.split_at(split);
+        let amount: f64 = number
+            .trim_end()
+            .parse()
+            .map_err(|_| ParseLengthError::InvalidNumber(s.to_string()))?;
+        if !amount.is_finite() {
+            return Err(ParseLengthError::InvalidNumber(s.to_string()));
+        }
+        let length = match unit.to_ascii_lowercase().as_str() {
+            "emu" => Length::Emu(amount),
+            "in" | "inch" | "inches" => Length::Inches(amount),
+            "cm" => Length::Cm(amount),
+            "mm" => Length::Mm(amount),
+            "pt" => Length::Pt(amount),
+            "tw" | "twip" | "twips" => Length::Twips(amount),
+            "pc" => Length::Pc(amount),
+            "px" => Length::css_px(amount),
+            "em" => Length::Em {
+                value: amount,
+                font_pt: DEFAULT_FONT_PT,
+            },
+            unit => {
+                if let Some(dpi) = unit.strip_prefix("px@") {
+                    let dpi = _positive(dpi.strip_suffix("dpi"))
+                        .ok_or_else(|| ParseLengthError::InvalidDpi(unit.to_string()))?;
+                    Length::Px { value: amount, dpi }
+                } else if let Some(font_pt) = unit.strip_prefix("em@") {
+                    let font_pt = _positive(font_pt.strip_suffix("pt"))
+                        .ok_or_else(|| ParseLengthError::InvalidFontSize(unit.to_string()))?;
+                    Length::Em {
+                        value: amount,
+                        font_pt,
+                    }
+                } else {
+                    return Err(ParseLengthError::UnknownUnit(unit.to_string()));
+                }
+            }
+        };
+        Ok(length)
+    }
+}
+
+/// A finite, positive number, for resolutions and font sizes.
+fn _positive(value: Option<&str>) -> Option<f64> {
+    value
+        .and_then(|value| value.parse().ok())
+        .filter(|value: &f64| *value > 0.0 && value.is_finite())
+}
+
+/// Formats the amount followed by the unit suffix accepted by `FromStr`, e.g. `2.5in` or
+/// `300px@96dpi`. A precision (`{:.2}`) applies to the amount.
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self {
+            Length::Emu(_) => "emu".to_string(),
+            Length::Inches(_) => "in".to_string(),
+            Length::Cm(_) => "cm".to_string(),
+            Length::Mm(_) => "mm".to_string(),
+            Length::Pt(_) => "pt".to_string(),
+            Length::Twips(_) => "tw".to_string(),
+            Length::Pc(_) => "pc".to_string(),
+            Length::Px { dpi, .. } => format!("px@{}dpi", dpi),
+            Length::Em { font_pt, .. } => format!("em@{}pt", font_pt),
+        };
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*}{}", precision, self.amount(), unit),
+            None => write!(f, "{}{}", self.amount(), unit),
         }
     }
 }
@@ -1,30 +1,35 @@
+#[cfg(feature = "tokio")]
+mod async_probe;
 pub mod bmp;
+pub mod error;
+pub mod fit;
+pub mod format;
 pub mod gif;
+pub mod heif;
 pub mod jpeg;
 pub mod length;
+pub mod ooxml;
 pub mod png;
+pub mod probe;
+pub mod scan;
 pub mod tiff;
+pub mod webp;
 
 use crate::length::Length;
-use std::io::{BufRead, Cursor, Read, Seek};
-use thiserror::Error;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
 use crate::bmp::Bmp;
+pub use crate::error::{Error, Result};
+use crate::format::{Format, SNIFF_LEN};
 use crate::gif::Gif;
+use crate::heif::Heif;
 use crate::jpeg::Jpeg;
 use crate::png::Png;
+use crate::probe::{LimitReader, ParseOptions, ProbeOptions};
 use crate::tiff::Tiff;
+use crate::webp::Webp;
 
-#[derive(Debug, Error)]
-pub enum Error {
-    #[error("IO error: {0}")]
-    IOError(#[from] std::io::Error),
-    #[error("Common error: {0}")]
-    CommonError(String),
-    #[error("Error reading file: {0}")]
-    ParseError(#[from] binrw::Error)
-}
-pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Image {
     pub crc32: u32,
     pub dimensions: (u32, u32),
@@ -32,8 +37,15 @@ pub struct Image {
     pub height: Length,
     pub x_dpi: u32,
     pub y_dpi: u32,
+    /// EXIF orientation (1-8); 1 means the stored pixels are already upright.
+    pub orientation: u16,
     pub content_type: String,
     pub ext: String,
+    pub format: Format,
+    /// Set when the file name carries an extension that does not match the detected format.
+    pub ext_mismatch: bool,
+    /// See `ImageReader::is_animated`.
+    pub animated: bool,
 }
 
 #[derive(Debug)]
@@ -43,55 +55,190 @@ pub enum ImageType {
     Jpeg(Jpeg),
     Png(Png),
     Tiff(Tiff),
+    Webp(Webp),
+    Avif(Heif),
+    Heif(Heif),
 }
 
 impl Image {
     pub fn from_file(path: &str) -> Result<Image> {
-        let ext = path.clone().split('.').last().ok_or(Error::CommonError("文件名不正确".to_string()))?.to_lowercase();
+        Self::from_file_with(path, &ParseOptions::default())
+    }
+
+    /// Like `from_file`, with options such as `ParseOptions::strict`.
+    pub fn from_file_with(path: &str, options: &ParseOptions) -> Result<Image> {
         let data = std::fs::read(path)?;
-        let crc32 = const_crc32::crc32(&data);
-        let mut reader = Cursor::new(data);
-        let r#type = match ext.as_str() {
-            "bmp" => Ok(ImageType::Bmp(Bmp::new(&mut reader)?)),
-            "gif" => Ok(ImageType::Gif(Gif::new(&mut reader)?)),
-            "jpeg" | "jpg" => Ok(ImageType::Jpeg(Jpeg::new(&mut reader)?)),
-            "png" => Ok(ImageType::Png(Png::new(&mut reader)?)),
-            "tiff" | "tif" => Ok(ImageType::Tiff(Tiff::new(&mut reader)?)),
-            _ => Err(Error::CommonError("不支持的格式".to_string())),
-        }?;
-
-        Ok(Self {
+        let mut image = Self::from_bytes_with(&data, options)?;
+        image.check_ext(path);
+        Ok(image)
+    }
+
+    /// Reads the remainder of `reader`, starting at its current position.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Image> {
+        Self::from_reader_with(reader, &ParseOptions::default())
+    }
+
+    /// Like `from_reader`, with options such as `ParseOptions::strict`.
+    pub fn from_reader_with<R: Read + Seek>(reader: &mut R, options: &ParseOptions) -> Result<Image> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::from_bytes_with(&data, options)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Image> {
+        Self::from_bytes_with(data, &ParseOptions::default())
+    }
+
+    /// Like `from_bytes`, with options such as `ParseOptions::strict`.
+    pub fn from_bytes_with(data: &[u8], options: &ParseOptions) -> Result<Image> {
+        let crc32 = const_crc32::crc32(data);
+        let format = Format::detect(data).ok_or(Error::UnsupportedFormat)?;
+        let r#type = ImageType::parse_with(format, &mut Cursor::new(data), options)?;
+        Ok(Self::new(&r#type, crc32))
+    }
+
+    /// Returns a copy of `data` with its resolution metadata set to `x_dpi` by `y_dpi`,
+    /// without touching the pixel data. Supports BMP, JPEG, PNG and TIFF.
+    pub fn set_dpi(data: &[u8], x_dpi: u32, y_dpi: u32) -> Result<Vec<u8>> {
+        match Format::detect(data) {
+            Some(Format::Bmp) => bmp::set_dpi(data, x_dpi, y_dpi),
+            Some(Format::Jpeg) => jpeg::set_dpi(data, x_dpi, y_dpi),
+            Some(Format::Png) => png::set_dpi(data, x_dpi, y_dpi),
+            Some(Format::Tiff) => tiff::set_dpi(data, x_dpi, y_dpi),
+            _ => Err(Error::UnsupportedFormat),
+        }
+    }
+
+    /// Like `from_file`, but only reads the header. See `probe_reader`.
+    pub fn probe_file(path: &str, options: &ProbeOptions) -> Result<Image> {
+        let mut file = std::fs::File::open(path)?;
+        let mut image = Self::probe_reader(&mut file, options)?;
+        image.check_ext(path);
+        Ok(image)
+    }
+
+    /// Parses the image starting at the current position of `reader`, stopping as soon as
+    /// the dimensions and resolution are known. `crc32` is left at 0 unless
+    /// `ProbeOptions::crc32` is set.
+    pub fn probe_reader<R: Read + Seek>(reader: &mut R, options: &ProbeOptions) -> Result<Image> {
+        let start = reader.stream_position()?;
+        let mut limited = BufReader::new(LimitReader::new(&mut *reader, options.max_bytes)?);
+        let mut magic = Vec::with_capacity(SNIFF_LEN);
+        (&mut limited).take(SNIFF_LEN as u64).read_to_end(&mut magic)?;
+        let len = Format::sniff_len(&magic);
+        (&mut limited).take((len - magic.len()) as u64).read_to_end(&mut magic)?;
+        let format = Format::detect(&magic).ok_or(Error::UnsupportedFormat)?;
+        limited.seek(SeekFrom::Start(0))?;
+        let r#type = ImageType::probe_with(format, &mut limited, options)?;
+        drop(limited);
+
+        let mut crc32 = 0;
+        if options.crc32 {
+            reader.seek(SeekFrom::Start(start))?;
+            let mut buf = vec![0u8; 64 * 1024];
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                crc32 = const_crc32::crc32_seed(&buf[..n], crc32);
+            }
+        }
+        Ok(Self::new(&r#type, crc32))
+    }
+
+    /// Dimensions as the image should be displayed, swapped for the transposing
+    /// orientations 5-8.
+    pub fn display_dimensions(&self) -> (u32, u32) {
+        _display_dimension(self.dimensions, self.orientation)
+    }
+
+    fn new(r#type: &ImageType, crc32: u32) -> Image {
+        Self {
             crc32,
             dimensions: r#type.dimensions(),
             width: r#type.width(),
             height: r#type.height(),
             x_dpi: r#type.x_dpi(),
             y_dpi: r#type.y_dpi(),
+            orientation: r#type.orientation(),
             content_type: r#type.content_type().to_string(),
             ext: r#type.default_ext().to_string(),
-        })
+            format: r#type.format(),
+            ext_mismatch: false,
+            animated: r#type.is_animated(),
+        }
+    }
+
+    fn check_ext(&mut self, path: &str) {
+        self.ext_mismatch = std::path::Path::new(path)
+            .extension()
+            .is_some_and(|_| Format::from_path(path) != Some(self.format));
     }
 }
 
 impl ImageType {
-    pub fn content_type(&self) -> &'static str {
+    pub fn parse<R: BufRead + Seek>(format: Format, reader: &mut R) -> Result<ImageType> {
+        Self::parse_with(format, reader, &ParseOptions::default())
+    }
+
+    pub fn parse_with<R: BufRead + Seek>(
+        format: Format,
+        reader: &mut R,
+        options: &ParseOptions,
+    ) -> Result<ImageType> {
+        Ok(match format {
+            Format::Png if options.strict => ImageType::Png(Png::new_strict(reader)?),
+            Format::Bmp => ImageType::Bmp(Bmp::new(reader)?),
+            Format::Gif => ImageType::Gif(Gif::new(reader)?),
+            Format::Jpeg => ImageType::Jpeg(Jpeg::new(reader)?),
+            Format::Png => ImageType::Png(Png::new(reader)?),
+            Format::Tiff => ImageType::Tiff(Tiff::new(reader)?),
+            Format::Webp => ImageType::Webp(Webp::new(reader)?),
+            Format::Avif => ImageType::Avif(Heif::new(reader)?),
+            Format::Heic | Format::Heif => ImageType::Heif(Heif::new(reader)?),
+        })
+    }
+
+    /// Like `parse`, but reads incrementally and stops once the header fields are known.
+    pub fn probe<R: BufRead + Seek>(format: Format, reader: &mut R) -> Result<ImageType> {
+        Self::probe_with(format, reader, &ProbeOptions::default())
+    }
+
+    /// Like `probe`; `ProbeOptions::strict` makes PNG files be read and verified in full.
+    /// The byte budget is enforced by the caller's reader.
+    pub fn probe_with<R: BufRead + Seek>(
+        format: Format,
+        reader: &mut R,
+        options: &ProbeOptions,
+    ) -> Result<ImageType> {
+        Ok(match format {
+            Format::Png if options.strict => ImageType::Png(Png::new_strict(reader)?),
+            Format::Jpeg => ImageType::Jpeg(Jpeg::probe(reader)?),
+            Format::Png => ImageType::Png(Png::probe(reader)?),
+            Format::Webp => ImageType::Webp(Webp::probe(reader)?),
+            _ => Self::parse(format, reader)?,
+        })
+    }
+
+    pub fn format(&self) -> Format {
         match self {
-            ImageType::Bmp(_) => "image/bmp",
-            ImageType::Gif(_) => "image/gif",
-            ImageType::Jpeg(_) => "image/jpeg",
-            ImageType::Png(_) => "image/png",
-            ImageType::Tiff(_) => "image/tiff",
+            ImageType::Bmp(_) => Format::Bmp,
+            ImageType::Gif(_) => Format::Gif,
+            ImageType::Jpeg(_) => Format::Jpeg,
+            ImageType::Png(_) => Format::Png,
+            ImageType::Tiff(_) => Format::Tiff,
+            ImageType::Webp(_) => Format::Webp,
+            ImageType::Avif(r) | ImageType::Heif(r) => r.format(),
         }
     }
 
+    pub fn content_type(&self) -> &'static str {
+        self.format().content_type()
+    }
+
     pub fn default_ext(&self) -> &'static str {
-        match self {
-            ImageType::Bmp(_) => "bmp",
-            ImageType::Gif(_) => "gif",
-            ImageType::Jpeg(_) => "jpeg",
-            ImageType::Png(_) => "png",
-            ImageType::Tiff(_) => "tiff",
-        }
+        self.format().default_ext()
     }
 
     pub fn dimensions(&self) -> (u32, u32) {
@@ -101,6 +248,8 @@ impl ImageType {
             ImageType::Jpeg(r) => r.dimension(),
             ImageType::Png(r) => r.dimension(),
             ImageType::Tiff(r) => r.dimension(),
+            ImageType::Webp(r) => r.dimension(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.dimension(),
         }
     }
 
@@ -111,6 +260,8 @@ impl ImageType {
             ImageType::Jpeg(r) => r.width(),
             ImageType::Png(r) => r.width(),
             ImageType::Tiff(r) => r.width(),
+            ImageType::Webp(r) => r.width(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.width(),
         }
     }
 
@@ -121,6 +272,8 @@ impl ImageType {
             ImageType::Jpeg(r) => r.height(),
             ImageType::Png(r) => r.height(),
             ImageType::Tiff(r) => r.height(),
+            ImageType::Webp(r) => r.height(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.height(),
         }
     }
 
@@ -131,6 +284,8 @@ impl ImageType {
             ImageType::Jpeg(r) => r.x_dpi(),
             ImageType::Png(r) => r.x_dpi(),
             ImageType::Tiff(r) => r.x_dpi(),
+            ImageType::Webp(r) => r.x_dpi(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.x_dpi(),
         }
     }
 
@@ -141,6 +296,32 @@ impl ImageType {
             ImageType::Jpeg(r) => r.y_dpi(),
             ImageType::Png(r) => r.y_dpi(),
             ImageType::Tiff(r) => r.y_dpi(),
+            ImageType::Webp(r) => r.y_dpi(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.y_dpi(),
+        }
+    }
+
+    pub fn orientation(&self) -> u16 {
+        match self {
+            ImageType::Bmp(r) => r.orientation(),
+            ImageType::Gif(r) => r.orientation(),
+            ImageType::Jpeg(r) => r.orientation(),
+            ImageType::Png(r) => r.orientation(),
+            ImageType::Tiff(r) => r.orientation(),
+            ImageType::Webp(r) => r.orientation(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.orientation(),
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        match self {
+            ImageType::Bmp(r) => r.is_animated(),
+            ImageType::Gif(r) => r.is_animated(),
+            ImageType::Jpeg(r) => r.is_animated(),
+            ImageType::Png(r) => r.is_animated(),
+            ImageType::Tiff(r) => r.is_animated(),
+            ImageType::Webp(r) => r.is_animated(),
+            ImageType::Avif(r) | ImageType::Heif(r) => r.is_animated(),
         }
     }
 }
@@ -149,12 +330,34 @@ pub trait ImageReader {
     fn dimension(&self) -> (u32, u32);
 
     fn width(&self) -> Length {
-        Length::Inches((self.dimension().0 as f32) / (self.x_dpi() as f32))
+        Length::Inches((self.dimension().0 as f64) / (self.x_dpi() as f64))
     }
 
     fn height(&self) -> Length {
-        Length::Inches((self.dimension().1 as f32) / (self.y_dpi() as f32))
+        Length::Inches((self.dimension().1 as f64) / (self.y_dpi() as f64))
     }
     fn x_dpi(&self) -> u32;
     fn y_dpi(&self) -> u32;
+
+    /// EXIF orientation (1-8) of the stored pixels.
+    fn orientation(&self) -> u16 {
+        1
+    }
+
+    /// Whether the image has more than a single frame. Detected for APNG and WebP.
+    fn is_animated(&self) -> bool {
+        false
+    }
+
+    fn display_dimension(&self) -> (u32, u32) {
+        _display_dimension(self.dimension(), self.orientation())
+    }
+}
+
+fn _display_dimension((width, height): (u32, u32), orientation: u16) -> (u32, u32) {
+    if (5..=8).contains(&orientation) {
+        (height, width)
+    } else {
+        (width, height)
+    }
 }
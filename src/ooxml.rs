@@ -0,0 +1,195 @@
+use crate::length::{Length, Rounding};
+use crate::{Error, Image, Result};
+
+const NS_A: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const NS_PIC: &str = "http://schemas.openxmlformats.org/drawingml/2006/picture";
+/// Largest coordinate DrawingML accepts, in EMU (`ST_PositiveCoordinate`).
+const MAX_EMU: i64 = 27273042316900;
+
+/// A picture to embed in an Office document, rendered as a DrawingML fragment.
+///
+/// The fragments use the `wp`, `p`, `xdr` and `r` prefixes that Word documents, slides and
+/// spreadsheet drawings declare on their root element; `a` and `pic` are declared inline
+/// where those parts do not declare them.
+#[derive(Debug, Clone)]
+pub struct Picture {
+    /// Drawing object id, unique within the part.
+    pub id: u32,
+    /// Relationship id of the image part, e.g. `rId5`.
+    pub rel_id: String,
+    pub name: String,
+    /// Alternative text.
+    pub description: String,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Picture {
+    /// Uses the displayed physical size of `image`; replace `width` and `height` (e.g. with
+    /// the result of `Image::fit`) to scale it.
+    pub fn new(image: &Image, id: u32, rel_id: &str, name: &str) -> Picture {
+        let (width, height) = image.display_size();
+        Picture {
+            id,
+            rel_id: rel_id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Extents in whole EMU; fails with `Error::InvalidExtent` unless both are finite,
+    /// non-negative and within the DrawingML range.
+    pub fn extent(&self) -> Result<(i64, i64)> {
+        let extent = |length: Length| _emu(length).filter(|emu| *emu >= 0);
+        match (extent(self.width), extent(self.height)) {
+            (Some(cx), Some(cy)) => Ok((cx, cy)),
+            (None, _) => Err(Error::InvalidExtent(self.width)),
+            (_, None) => Err(Error::InvalidExtent(self.height)),
+        }
+    }
+
+    /// `wp:inline` for a picture in the text flow of a Word document.
+    pub fn docx_inline(&self) -> Result<String> {
+        let (cx, cy) = self.extent()?;
+        Ok(format!(
+            "<wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">\
+             <wp:extent cx=\"{cx}\" cy=\"{cy}\"/>\
+             <wp:effectExtent l=\"0\" t=\"0\" r=\"0\" b=\"0\"/>\
+             {doc_pr}{graphic}</wp:inline>",
+            doc_pr = self.doc_pr(),
+            graphic = self.graphic(cx, cy),
+        ))
+    }
+
+    /// `wp:anchor` for a floating picture in a Word document, offset from the column and
+    /// paragraph it is anchored to, with text wrapped around it.
+    pub fn docx_anchor(&self, x: Length, y: Length) -> Result<String> {
+        let (cx, cy) = self.extent()?;
+        let (x, y) = (_offset(x)?, _offset(y)?);
+        Ok(format!(
+            "<wp:anchor distT=\"0\" distB=\"0\" distL=\"114300\" distR=\"114300\" simplePos=\"0\" \
+             relativeHeight=\"0\" behindDoc=\"0\" locked=\"0\" layoutInCell=\"1\" allowOverlap=\"1\">\
+             <wp:simplePos x=\"0\" y=\"0\"/>\
+             <wp:positionH relativeFrom=\"column\"><wp:posOffset>{x}</wp:posOffset></wp:positionH>\
+             <wp:positionV relativeFrom=\"paragraph\"><wp:posOffset>{y}</wp:posOffset></wp:positionV>\
+             <wp:extent cx=\"{cx}\" cy=\"{cy}\"/>\
+             <wp:effectExtent l=\"0\" t=\"0\" r=\"0\" b=\"0\"/>\
+             <wp:wrapSquare wrapText=\"bothSides\"/>\
+             {doc_pr}{graphic}</wp:anchor>",
+            doc_pr = self.doc_pr(),
+            graphic = self.graphic(cx, cy),
+        ))
+    }
+
+    /// `p:pic` for a PowerPoint slide, with its top-left corner at `x`, `y`.
+    pub fn pptx(&self, x: Length, y: Length) -> Result<String> {
+        let (cx, cy) = self.extent()?;
+        let (x, y) = (_offset(x)?, _offset(y)?);
+        Ok(format!(
+            "<p:pic><p:nvPicPr>{c_nv_pr}\
+             <p:cNvPicPr><a:picLocks noChangeAspect=\"1\"/></p:cNvPicPr><p:nvPr/></p:nvPicPr>\
+             {blip_fill}{sp_pr}</p:pic>",
+            c_nv_pr = self.c_nv_pr("p", self.id),
+            blip_fill = self.blip_fill("p"),
+            sp_pr = self.sp_pr("p", x, y, cx, cy),
+        ))
+    }
+
+    /// `xdr:pic` for an Excel drawing, wrapped in the `xdr:oneCellAnchor` that places it at
+    /// the top-left corner of the zero-based cell `column`, `row`.
+    pub fn xlsx(&self, column: u32, row: u32) -> Result<String> {
+        let (cx, cy) = self.extent()?;
+        Ok(format!(
+            "<xdr:oneCellAnchor><xdr:from>\
+             <xdr:col>{column}</xdr:col><xdr:colOff>0</xdr:colOff>\
+             <xdr:row>{row}</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from>\
+             <xdr:ext cx=\"{cx}\" cy=\"{cy}\"/>\
+             <xdr:pic><xdr:nvPicPr>{c_nv_pr}\
+             <xdr:cNvPicPr><a:picLocks noChangeAspect=\"1\"/></xdr:cNvPicPr></xdr:nvPicPr>\
+             {blip_fill}{sp_pr}</xdr:pic><xdr:clientData/></xdr:oneCellAnchor>",
+            c_nv_pr = self.c_nv_pr("xdr", self.id),
+            blip_fill = self.blip_fill("xdr"),
+            sp_pr = self.sp_pr("xdr", 0, 0, cx, cy),
+        ))
+    }
+
+    fn doc_pr(&self) -> String {
+        format!(
+            "<wp:docPr id=\"{}\" name=\"{}\" descr=\"{}\"/>\
+             <wp:cNvGraphicFramePr>\
+             <a:graphicFrameLocks xmlns:a=\"{NS_A}\" noChangeAspect=\"1\"/>\
+             </wp:cNvGraphicFramePr>",
+            self.id,
+            escape(&self.name),
+            escape(&self.description),
+        )
+    }
+
+    fn graphic(&self, cx: i64, cy: i64) -> String {
+        format!(
+            "<a:graphic xmlns:a=\"{NS_A}\"><a:graphicData uri=\"{NS_PIC}\">\
+             <pic:pic xmlns:pic=\"{NS_PIC}\"><pic:nvPicPr>{c_nv_pr}<pic:cNvPicPr/></pic:nvPicPr>\
+             {blip_fill}{sp_pr}</pic:pic></a:graphicData></a:graphic>",
+            // The picture inside the frame is numbered separately from `wp:docPr`.
+            c_nv_pr = self.c_nv_pr("pic", 0),
+            blip_fill = self.blip_fill("pic"),
+            sp_pr = self.sp_pr("pic", 0, 0, cx, cy),
+        )
+    }
+
+    fn c_nv_pr(&self, prefix: &str, id: u32) -> String {
+        format!(
+            "<{prefix}:cNvPr id=\"{id}\" name=\"{}\" descr=\"{}\"/>",
+            escape(&self.name),
+            escape(&self.description),
+        )
+    }
+
+    fn blip_fill(&self, prefix: &str) -> String {
+        format!(
+            "<{prefix}:blipFill><a:blip r:embed=\"{}\"/>\
+             <a:stretch><a:fillRect/></a:stretch></{prefix}:blipFill>",
+            escape(&self.rel_id),
+        )
+    }
+
+    fn sp_pr(&self, prefix: &str, x: i64, y: i64, cx: i64, cy: i64) -> String {
+        format!(
+            "<{prefix}:spPr><a:xfrm><a:off x=\"{x}\" y=\"{y}\"/><a:ext cx=\"{cx}\" cy=\"{cy}\"/></a:xfrm>\
+             <a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></{prefix}:spPr>"
+        )
+    }
+}
+
+/// Whole EMU, or `None` outside the range DrawingML accepts.
+fn _emu(length: Length) -> Option<i64> {
+    let emu = Rounding::Nearest.apply(length.to_emu());
+    (emu.abs() <= MAX_EMU as f64).then_some(emu as i64)
+}
+
+fn _offset(length: Length) -> Result<i64> {
+    _emu(length).ok_or(Error::InvalidExtent(length))
+}
+
+/// Escapes text for use in XML attribute values and element content.
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            // Other control characters are not allowed in XML 1.0.
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
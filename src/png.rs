@@ -1,7 +1,10 @@
-use crate::ImageReader;
+use crate::error::Context;
+use crate::format::Format;
+use crate::{Error, ImageReader};
 use binrw::helpers::until;
 use binrw::BinRead;
-use std::io::{BufRead, Seek};
+use std::io::{BufRead, Read, Seek, SeekFrom};
+use std::time::Duration;
 
 #[derive(Debug, BinRead)]
 #[br(big)]
@@ -10,6 +13,18 @@ pub struct Png {
     #[br(ignore)]
     pub info: Option<Info>,
 
+    /// Entries of the `tEXt`, `zTXt` and `iTXt` chunks, in file order. Not filled by `probe`,
+    /// since text chunks may follow the image data.
+    #[br(ignore)]
+    pub text: Vec<Text>,
+
+    /// APNG animation control, `None` for still images.
+    #[br(ignore)]
+    pub animation: Option<Animation>,
+
+    #[br(ignore)]
+    pub color: ColorInfo,
+
     #[br(parse_with = until(|chunk: &Chunk| chunk.is_end()))]
     chunks: Vec<Chunk>,
 }
@@ -19,15 +34,22 @@ pub enum Chunk {
     IHDR(IHDRChunk),
     IEND(IENDChunk),
     PHYS(PHYSChunk),
+    TRNS(TRNSChunk),
+    Text(TextChunk),
+    ACTL(ACTLChunk),
+    FCTL(FCTLChunk),
+    FDAT(FDATChunk),
+    ICCP(ICCPChunk),
+    SRGB(SRGBChunk),
+    GAMA(GAMAChunk),
+    CHRM(CHRMChunk),
+    CICP(CICPChunk),
     Other(OtherChunk),
 }
 
 impl Chunk {
     pub fn is_end(&self) -> bool {
-        match self {
-            Chunk::IEND(_) => true,
-            _ => false,
-        }
+        matches!(self, Chunk::IEND(_))
     }
 }
 
@@ -38,10 +60,68 @@ pub struct IHDRChunk {
     #[br(magic(b"IHDR"))]
     pub width: u32,
     pub height: u32,
-    #[br(count = length - 8)]
-    data: Vec<u8>,
+    #[br(assert(length == 13, "IHDR length must be 13, not {}", length))]
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression: CompressionMethod,
+    pub filter: FilterMethod,
+    pub interlace: InterlaceMethod,
     pub crc: u32,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorType {
+    #[default]
+    #[br(magic(0u8))]
+    Grayscale,
+    #[br(magic(2u8))]
+    Rgb,
+    #[br(magic(3u8))]
+    Indexed,
+    #[br(magic(4u8))]
+    GrayscaleAlpha,
+    #[br(magic(6u8))]
+    Rgba,
+    Other(u8),
+}
+
+impl ColorType {
+    /// Whether every pixel carries an alpha sample.
+    pub fn has_alpha_channel(&self) -> bool {
+        matches!(self, ColorType::GrayscaleAlpha | ColorType::Rgba)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompressionMethod {
+    #[default]
+    #[br(magic(0u8))]
+    Deflate,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilterMethod {
+    /// Per-scanline filter types 0-4.
+    #[default]
+    #[br(magic(0u8))]
+    Adaptive,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterlaceMethod {
+    #[default]
+    #[br(magic(0u8))]
+    None,
+    #[br(magic(1u8))]
+    Adam7,
+    Other(u8),
+}
 #[derive(Debug, BinRead)]
 #[br(big)]
 pub struct IENDChunk {
@@ -60,6 +140,397 @@ pub struct PHYSChunk {
     pub crc: u32,
 }
 
+/// Transparency: a single transparent color, or alpha values for palette entries.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct TRNSChunk {
+    pub length: u32,
+    #[br(magic(b"tRNS"))]
+    #[br(count = length)]
+    pub data: Vec<u8>,
+    pub crc: u32,
+}
+
+/// APNG animation control.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct ACTLChunk {
+    pub length: u32,
+    #[br(magic(b"acTL"))]
+    #[br(assert(length == 8, "acTL length must be 8, not {}", length))]
+    pub num_frames: u32,
+    pub num_plays: u32,
+    pub crc: u32,
+}
+
+/// APNG frame control, preceding the data of each frame.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct FCTLChunk {
+    pub length: u32,
+    #[br(magic(b"fcTL"))]
+    #[br(assert(length == 26, "fcTL length must be 26, not {}", length))]
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+    pub crc: u32,
+}
+
+/// APNG frame data; only the sequence number is kept.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct FDATChunk {
+    pub length: u32,
+    #[br(magic(b"fdAT"))]
+    #[br(assert(length >= 4, "fdAT length must be at least 4, not {}", length))]
+    #[br(pad_after = length.saturating_sub(4))]
+    pub sequence_number: u32,
+    pub crc: u32,
+}
+
+/// How the frame area is treated before the next frame is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisposeOp {
+    #[br(magic(0u8))]
+    None,
+    /// Cleared to fully transparent black.
+    #[br(magic(1u8))]
+    Background,
+    /// Reverted to its contents before the frame.
+    #[br(magic(2u8))]
+    Previous,
+    Other(u8),
+}
+
+/// How the frame is combined with the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendOp {
+    /// Replaces the frame area, including alpha.
+    #[br(magic(0u8))]
+    Source,
+    /// Alpha-composited over the frame area.
+    #[br(magic(1u8))]
+    Over,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Animation {
+    /// Frame count declared by `acTL`.
+    pub num_frames: u32,
+    /// Times to play the animation; 0 loops forever.
+    pub num_plays: u32,
+    /// Frames in sequence order. Empty when read by `Png::probe`, which stops at the
+    /// image data.
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Sum of the delays of all frames.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(Frame::delay).sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+    /// Whether the frame is the default image (`IDAT`) rather than `fdAT` data.
+    pub is_default_image: bool,
+}
+
+impl Frame {
+    /// Display time of the frame; a zero denominator means hundredths of a second.
+    pub fn delay(&self) -> Duration {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+    }
+}
+
+/// Embedded ICC profile: a Latin-1 name, a compression method and the deflated profile.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct ICCPChunk {
+    pub length: u32,
+    #[br(magic(b"iCCP"))]
+    #[br(count = length)]
+    pub data: Vec<u8>,
+    pub crc: u32,
+}
+
+impl ICCPChunk {
+    /// Decodes the chunk; `None` when it is malformed or the profile cannot be inflated.
+    pub fn profile(&self) -> Option<IccProfile> {
+        let (name, rest) = split_nul(&self.data)?;
+        let (&method, compressed) = rest.split_first()?;
+        if method != 0 {
+            return None;
+        }
+        Some(IccProfile {
+            name: latin1(name),
+            data: inflate(compressed, MAX_INFLATED_LEN)?,
+        })
+    }
+}
+
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct SRGBChunk {
+    pub length: u32,
+    #[br(magic(b"sRGB"))]
+    #[br(assert(length == 1, "sRGB length must be 1, not {}", length))]
+    pub intent: RenderingIntent,
+    pub crc: u32,
+}
+
+/// Image gamma times 100000.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct GAMAChunk {
+    pub length: u32,
+    #[br(magic(b"gAMA"))]
+    #[br(assert(length == 4, "gAMA length must be 4, not {}", length))]
+    pub gamma: u32,
+    pub crc: u32,
+}
+
+/// CIE 1931 xy chromaticities of the white point and primaries, times 100000.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct CHRMChunk {
+    pub length: u32,
+    #[br(magic(b"cHRM"))]
+    #[br(assert(length == 32, "cHRM length must be 32, not {}", length))]
+    pub white_x: u32,
+    pub white_y: u32,
+    pub red_x: u32,
+    pub red_y: u32,
+    pub green_x: u32,
+    pub green_y: u32,
+    pub blue_x: u32,
+    pub blue_y: u32,
+    pub crc: u32,
+}
+
+/// Coding-independent code points (ITU-T H.273), used to signal HDR content.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct CICPChunk {
+    pub length: u32,
+    #[br(magic(b"cICP"))]
+    #[br(assert(length == 4, "cICP length must be 4, not {}", length))]
+    pub colour_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub video_full_range: u8,
+    pub crc: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RenderingIntent {
+    #[br(magic(0u8))]
+    Perceptual,
+    #[br(magic(1u8))]
+    RelativeColorimetric,
+    #[br(magic(2u8))]
+    Saturation,
+    #[br(magic(3u8))]
+    AbsoluteColorimetric,
+    Other(u8),
+}
+
+/// Color space information from `iCCP`, `sRGB`, `gAMA`, `cHRM` and `cICP`; each field is
+/// `None` when its chunk is absent.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorInfo {
+    pub icc_profile: Option<IccProfile>,
+    /// Present when the image is in the sRGB color space.
+    pub srgb_intent: Option<RenderingIntent>,
+    pub gamma: Option<f64>,
+    pub chromaticities: Option<Chromaticities>,
+    pub cicp: Option<Cicp>,
+}
+
+impl ColorInfo {
+    fn update(&mut self, chunk: &Chunk) {
+        let scaled = |value: u32| value as f64 / 100000.0;
+        match chunk {
+            Chunk::ICCP(chunk) => self.icc_profile = chunk.profile(),
+            Chunk::SRGB(chunk) => self.srgb_intent = Some(chunk.intent),
+            Chunk::GAMA(chunk) => self.gamma = Some(scaled(chunk.gamma)),
+            Chunk::CHRM(chunk) => {
+                self.chromaticities = Some(Chromaticities {
+                    white: (scaled(chunk.white_x), scaled(chunk.white_y)),
+                    red: (scaled(chunk.red_x), scaled(chunk.red_y)),
+                    green: (scaled(chunk.green_x), scaled(chunk.green_y)),
+                    blue: (scaled(chunk.blue_x), scaled(chunk.blue_y)),
+                })
+            }
+            Chunk::CICP(chunk) => {
+                self.cicp = Some(Cicp {
+                    colour_primaries: chunk.colour_primaries,
+                    transfer_function: chunk.transfer_function,
+                    matrix_coefficients: chunk.matrix_coefficients,
+                    video_full_range: chunk.video_full_range == 1,
+                })
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IccProfile {
+    pub name: String,
+    /// The inflated profile.
+    pub data: Vec<u8>,
+}
+
+/// CIE 1931 (x, y) coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chromaticities {
+    pub white: (f64, f64),
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+}
+
+/// Code points as defined by ITU-T H.273, e.g. primaries 9 (BT.2020) with transfer
+/// function 16 (PQ) for HDR10.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cicp {
+    pub colour_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub video_full_range: bool,
+}
+
+/// A `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, BinRead)]
+#[br(big)]
+pub struct TextChunk {
+    pub length: u32,
+    #[br(assert(matches!(&chunk_type, b"tEXt" | b"zTXt" | b"iTXt")))]
+    pub chunk_type: [u8; 4],
+    #[br(count = length)]
+    pub data: Vec<u8>,
+    pub crc: u32,
+}
+
+/// Decompressed text and ICC profiles are capped at this many bytes.
+const MAX_INFLATED_LEN: u64 = 16 * 1024 * 1024;
+
+impl TextChunk {
+    /// Decodes the chunk; `None` when it is malformed or its compressed text cannot be
+    /// inflated.
+    pub fn text(&self) -> Option<Text> {
+        let (keyword, rest) = split_nul(&self.data)?;
+        let keyword = latin1(keyword);
+        match &self.chunk_type {
+            b"tEXt" => Some(Text {
+                keyword,
+                value: latin1(rest),
+                compressed: false,
+                language: None,
+                translated_keyword: None,
+            }),
+            b"zTXt" => {
+                let (&method, compressed) = rest.split_first()?;
+                if method != 0 {
+                    return None;
+                }
+                Some(Text {
+                    keyword,
+                    value: latin1(&inflate(compressed, MAX_INFLATED_LEN)?),
+                    compressed: true,
+                    language: None,
+                    translated_keyword: None,
+                })
+            }
+            _ => {
+                let (&[flag, method], rest) = rest.split_first_chunk::<2>()?;
+                let (language, rest) = split_nul(rest)?;
+                let (translated_keyword, value) = split_nul(rest)?;
+                let value = match (flag, method) {
+                    (0, _) => value.to_vec(),
+                    (1, 0) => inflate(value, MAX_INFLATED_LEN)?,
+                    _ => return None,
+                };
+                Some(Text {
+                    keyword,
+                    value: String::from_utf8_lossy(&value).into_owned(),
+                    compressed: flag == 1,
+                    language: Some(latin1(language)),
+                    translated_keyword: Some(
+                        String::from_utf8_lossy(translated_keyword).into_owned(),
+                    ),
+                })
+            }
+        }
+    }
+}
+
+/// A keyword/value pair from a text chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Text {
+    pub keyword: String,
+    pub value: String,
+    /// Whether the value was stored deflated (`zTXt`, or compressed `iTXt`).
+    pub compressed: bool,
+    /// `iTXt` only: RFC 3066 language tag, possibly empty.
+    pub language: Option<String>,
+    /// `iTXt` only: the keyword translated into `language`, possibly empty.
+    pub translated_keyword: Option<String>,
+}
+
+fn split_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let nul = data.iter().position(|&b| b == 0)?;
+    Some((&data[..nul], &data[nul + 1..]))
+}
+
+/// Latin-1 maps every byte to the code point of the same value.
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|&b| b as char).collect()
+}
+
+/// Inflates zlib `data`, failing when it is corrupt or larger than `limit` bytes.
+fn inflate(data: &[u8], limit: u64) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut decoder = flate2::read::ZlibDecoder::new(data).take(limit + 1);
+    decoder.read_to_end(&mut out).ok()?;
+    if out.len() as u64 > limit {
+        return None;
+    }
+    Some(out)
+}
+
 #[derive(Debug, BinRead)]
 #[br(big)]
 pub struct OtherChunk {
@@ -70,7 +541,15 @@ pub struct OtherChunk {
     pub crc: u32,
 }
 
+#[derive(Debug, BinRead)]
+#[br(big)]
+struct ChunkHeader {
+    length: u32,
+    chunk_type: [u8; 4],
+}
+
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Info {
     pub width: u32,
 
@@ -81,29 +560,219 @@ pub struct Info {
     pub y_ppu: u32,
 
     pub unit: u32,
+
+    pub bit_depth: u8,
+
+    pub color_type: ColorType,
+
+    pub compression: CompressionMethod,
+
+    pub filter: FilterMethod,
+
+    pub interlace: InterlaceMethod,
+
+    /// Set when a `tRNS` chunk is present.
+    pub transparency: bool,
+}
+
+impl Info {
+    /// Whether any pixel can be transparent, through an alpha channel or a `tRNS` chunk.
+    pub fn has_alpha(&self) -> bool {
+        self.color_type.has_alpha_channel() || self.transparency
+    }
+
+    fn set_header(&mut self, chunk: &IHDRChunk) {
+        self.width = chunk.width;
+        self.height = chunk.height;
+        self.bit_depth = chunk.bit_depth;
+        self.color_type = chunk.color_type;
+        self.compression = chunk.compression;
+        self.filter = chunk.filter;
+        self.interlace = chunk.interlace;
+    }
 }
 
 impl Png {
+    /// Fails with `Error::CorruptChunk` when there is no valid `IHDR` chunk.
     pub fn new<R: BufRead + Seek>(reader: &mut R) -> crate::Result<Self> {
-        let mut png = Png::read(reader)?;
+        let mut png = Png::read(reader).context(Format::Png, reader)?;
         let mut info = Info::default();
+        let mut seen_header = false;
+        let mut seen_idat = false;
 
         png.chunks.iter().for_each(|c| {
             if let Chunk::IHDR(chunk) = c {
-                info.width = chunk.width;
-                info.height = chunk.height;
+                info.set_header(chunk);
+                seen_header = true;
             } else if let Chunk::PHYS(chunk) = c {
                 info.x_ppu = chunk.x_ppm;
                 info.y_ppu = chunk.y_ppm;
                 info.unit = chunk.unit as u32;
+            } else if let Chunk::TRNS(_) = c {
+                info.transparency = true;
+            } else if let Chunk::Text(chunk) = c {
+                png.text.extend(chunk.text());
+            } else if let Chunk::ACTL(chunk) = c {
+                png.animation = Some(Animation {
+                    num_frames: chunk.num_frames,
+                    num_plays: chunk.num_plays,
+                    frames: Vec::new(),
+                });
+            } else if let Chunk::FCTL(chunk) = c {
+                if let Some(animation) = &mut png.animation {
+                    animation.frames.push(Frame {
+                        width: chunk.width,
+                        height: chunk.height,
+                        x_offset: chunk.x_offset,
+                        y_offset: chunk.y_offset,
+                        delay_num: chunk.delay_num,
+                        delay_den: chunk.delay_den,
+                        dispose_op: chunk.dispose_op,
+                        blend_op: chunk.blend_op,
+                        // Only a frame control placed before IDAT describes the default image.
+                        is_default_image: !seen_idat,
+                    });
+                }
+            } else if let Chunk::Other(chunk) = c {
+                seen_idat |= &chunk.chunk_type == b"IDAT";
+            } else {
+                png.color.update(c);
             }
         });
+        if !seen_header {
+            return Err(_missing_header());
+        }
         png.info = Some(info);
         Ok(png)
     }
+
+    /// Like `new`, but first verifies the CRC-32 of every chunk up to `IEND` and fails with
+    /// `Error::ChecksumMismatch` for the first chunk whose CRC does not match.
+    pub fn new_strict<R: BufRead + Seek>(reader: &mut R) -> crate::Result<Self> {
+        let start = reader.stream_position()?;
+        Self::check_crc(reader)?;
+        reader.seek(SeekFrom::Start(start))?;
+        Self::new(reader)
+    }
+
+    fn check_crc<R: BufRead + Seek>(reader: &mut R) -> crate::Result<()> {
+        let start = reader.stream_position()?;
+        <[u8; 8]>::read(reader).context(Format::Png, reader)?;
+        let mut buf = vec![0u8; 8 * 1024];
+        loop {
+            let offset = reader.stream_position()? - start;
+            let header = ChunkHeader::read(reader).context(Format::Png, reader)?;
+            let mut crc = const_crc32::crc32(&header.chunk_type);
+            let mut remaining = header.length as usize;
+            while remaining > 0 {
+                let n = remaining.min(buf.len());
+                reader
+                    .read_exact(&mut buf[..n])
+                    .map_err(|err| match err.kind() {
+                        std::io::ErrorKind::UnexpectedEof => Error::Truncated {
+                            format: Format::Png,
+                            offset,
+                        },
+                        _ => err.into(),
+                    })?;
+                crc = const_crc32::crc32_seed(&buf[..n], crc);
+                remaining -= n;
+            }
+            let expected = u32::read_be(reader).context(Format::Png, reader)?;
+            if crc != expected {
+                return Err(Error::ChecksumMismatch {
+                    format: Format::Png,
+                    chunk: String::from_utf8_lossy(&header.chunk_type).into_owned(),
+                    offset,
+                });
+            }
+            if &header.chunk_type == b"IEND" {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads chunk by chunk and stops at the first `IDAT`, skipping over the data of
+    /// chunks it does not need. `pHYs`, `tRNS` and the color chunks must precede `IDAT`, so
+    /// the result matches `new` apart from `text` and the frames of `animation`.
+    pub fn probe<R: BufRead + Seek>(reader: &mut R) -> crate::Result<Self> {
+        let signature = <[u8; 8]>::read(reader).context(Format::Png, reader)?;
+        if &signature != b"\x89PNG\x0D\x0A\x1A\x0A" {
+            return Err(Error::InvalidMagic {
+                format: Format::Png,
+                offset: 0,
+            });
+        }
+
+        let mut info = Info::default();
+        let mut seen_header = false;
+        let mut animation = None;
+        let mut color = ColorInfo::default();
+        loop {
+            let start = reader.stream_position()?;
+            let header = ChunkHeader::read(reader).context(Format::Png, reader)?;
+            match &header.chunk_type {
+                b"IHDR" => {
+                    reader.seek(SeekFrom::Start(start))?;
+                    let chunk = IHDRChunk::read(reader).context(Format::Png, reader)?;
+                    info.set_header(&chunk);
+                    seen_header = true;
+                }
+                b"pHYs" => {
+                    reader.seek(SeekFrom::Start(start))?;
+                    let chunk = PHYSChunk::read(reader).context(Format::Png, reader)?;
+                    info.x_ppu = chunk.x_ppm;
+                    info.y_ppu = chunk.y_ppm;
+                    info.unit = chunk.unit as u32;
+                }
+                b"IDAT" | b"IEND" => break,
+                b"iCCP" | b"sRGB" | b"gAMA" | b"cHRM" | b"cICP" => {
+                    reader.seek(SeekFrom::Start(start))?;
+                    let chunk = Chunk::read(reader).context(Format::Png, reader)?;
+                    color.update(&chunk);
+                }
+                b"acTL" => {
+                    reader.seek(SeekFrom::Start(start))?;
+                    if let Chunk::ACTL(chunk) = Chunk::read(reader).context(Format::Png, reader)? {
+                        animation = Some(Animation {
+                            num_frames: chunk.num_frames,
+                            num_plays: chunk.num_plays,
+                            frames: Vec::new(),
+                        });
+                    }
+                }
+                b"tRNS" => {
+                    info.transparency = true;
+                    reader.seek(SeekFrom::Current(header.length as i64 + 4))?;
+                }
+                _ => {
+                    reader.seek(SeekFrom::Current(header.length as i64 + 4))?;
+                }
+            }
+        }
+        if !seen_header {
+            return Err(_missing_header());
+        }
+
+        Ok(Png {
+            info: Some(info),
+            text: Vec::new(),
+            animation,
+            color,
+            chunks: Vec::new(),
+        })
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.info.as_ref().is_some_and(Info::has_alpha)
+    }
 }
 
 impl ImageReader for Png {
+    fn is_animated(&self) -> bool {
+        self.animation.is_some()
+    }
+
     fn dimension(&self) -> (u32, u32) {
         if let Some(x) = &self.info {
             (x.width, x.height)
@@ -129,6 +798,15 @@ impl ImageReader for Png {
     }
 }
 
+/// `IHDR` must be the first chunk, right after the signature.
+fn _missing_header() -> Error {
+    Error::CorruptChunk {
+        format: Format::Png,
+        offset: 8,
+        reason: "missing or invalid IHDR chunk".to_string(),
+    }
+}
+
 fn _dpi(unit: u32, ppm: u32) -> u32 {
     if unit == 1 {
         (ppm as f32 * 0.0254).round() as u32
@@ -136,3 +814,63 @@ fn _dpi(unit: u32, ppm: u32) -> u32 {
         72
     }
 }
+
+/// Returns a copy of `data` with its `pHYs` chunk set to `x_dpi` by `y_dpi`, inserting the
+/// chunk after `IHDR` when there is none.
+pub fn set_dpi(data: &[u8], x_dpi: u32, y_dpi: u32) -> crate::Result<Vec<u8>> {
+    let ppm = |dpi: u32| {
+        let ppm = (dpi as f64 / 0.0254).round();
+        if dpi == 0 || ppm > u32::MAX as f64 {
+            return Err(Error::InvalidResolution {
+                format: Format::Png,
+                dpi,
+            });
+        }
+        Ok(ppm as u32)
+    };
+    let mut chunk = Vec::with_capacity(21);
+    chunk.extend_from_slice(&9u32.to_be_bytes());
+    chunk.extend_from_slice(b"pHYs");
+    chunk.extend_from_slice(&ppm(x_dpi)?.to_be_bytes());
+    chunk.extend_from_slice(&ppm(y_dpi)?.to_be_bytes());
+    chunk.push(1);
+    chunk.extend_from_slice(&const_crc32::crc32(&chunk[4..]).to_be_bytes());
+
+    let reader = &mut std::io::Cursor::new(data);
+    let signature = <[u8; 8]>::read(reader).context(Format::Png, reader)?;
+    if &signature != b"\x89PNG\x0D\x0A\x1A\x0A" {
+        return Err(Error::InvalidMagic {
+            format: Format::Png,
+            offset: 0,
+        });
+    }
+    let mut insert_at = None;
+    loop {
+        let start = reader.stream_position()?;
+        let header = ChunkHeader::read(reader).context(Format::Png, reader)?;
+        let end = start + 12 + header.length as u64;
+        match &header.chunk_type {
+            b"IHDR" => insert_at = Some(end as usize),
+            b"pHYs" if end <= data.len() as u64 => {
+                let mut out = data[..start as usize].to_vec();
+                out.extend_from_slice(&chunk);
+                out.extend_from_slice(&data[end as usize..]);
+                return Ok(out);
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+        reader.seek(SeekFrom::Start(end))?;
+    }
+    let insert_at = insert_at.ok_or_else(|| Error::CorruptChunk {
+        format: Format::Png,
+        offset: 8,
+        reason: "missing IHDR chunk".to_string(),
+    })?;
+
+    let mut out = Vec::with_capacity(data.len() + chunk.len());
+    out.extend_from_slice(&data[..insert_at]);
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&data[insert_at..]);
+    Ok(out)
+}
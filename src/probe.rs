@@ -0,0 +1,93 @@
+use std::io::{Read, Seek, SeekFrom};
+
+/// Controls how much of the source `Image::probe_reader` may touch.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeOptions {
+    /// Furthest offset (relative to the start of the image) the parsers may read up to.
+    /// `None` means no limit.
+    pub max_bytes: Option<u64>,
+    /// Computes `Image::crc32`, which requires reading the whole source once the header
+    /// has been parsed. The byte budget does not apply to this pass.
+    pub crc32: bool,
+    /// Verifies the CRC-32 of every PNG chunk, see `Png::new_strict`. This reads the
+    /// whole PNG up to `IEND`, within the byte budget.
+    pub strict: bool,
+}
+
+/// Controls how `Image::from_bytes_with` and friends parse the whole source.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Verifies the CRC-32 of every PNG chunk, see `Png::new_strict`.
+    pub strict: bool,
+}
+
+/// Payload of the `io::Error` returned by `LimitReader` once the budget is spent.
+#[derive(Debug)]
+pub(crate) struct LimitError(pub(crate) u64);
+
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "read limit of {} bytes exceeded", self.0)
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+/// Reader that presents `inner` from its current position onwards as a stream starting at
+/// offset 0, and refuses to read past `limit`.
+pub(crate) struct LimitReader<R> {
+    inner: R,
+    start: u64,
+    pos: u64,
+    limit: Option<u64>,
+}
+
+impl<R: Read + Seek> LimitReader<R> {
+    pub(crate) fn new(mut inner: R, limit: Option<u64>) -> std::io::Result<Self> {
+        let start = inner.stream_position()?;
+        Ok(Self {
+            inner,
+            start,
+            pos: 0,
+            limit,
+        })
+    }
+}
+
+impl<R: Read> Read for LimitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let buf = match self.limit {
+            Some(limit) => {
+                let remaining = limit.saturating_sub(self.pos);
+                if remaining == 0 && !buf.is_empty() {
+                    return Err(std::io::Error::other(LimitError(limit)));
+                }
+                let len = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+                &mut buf[..len]
+            }
+            None => buf,
+        };
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for LimitReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            other => other,
+        };
+        let absolute = self.inner.seek(pos)?;
+        if absolute < self.start {
+            self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        }
+        self.pos = absolute - self.start;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::format::{Format, SNIFF_LEN};
+use crate::probe::{ParseOptions, ProbeOptions};
+use crate::{Image, Result};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Formats to report; `None` reports every file whose format can be detected.
+    pub formats: Option<Vec<Format>>,
+    /// Number of worker threads; 0 uses the available parallelism.
+    pub workers: usize,
+    /// Reads only the headers (see `Image::probe_reader`) instead of whole files.
+    pub probe: Option<ProbeOptions>,
+    /// Options for reading whole files, used when `probe` is `None`.
+    pub parse: ParseOptions,
+}
+
+/// Outcome for a single file, or for a directory that could not be read.
+#[derive(Debug)]
+pub struct ScanEntry {
+    pub path: PathBuf,
+    pub result: Result<Image>,
+}
+
+/// Iterator over the entries produced by `scan`, in completion order.
+pub struct Scan {
+    results: Receiver<ScanEntry>,
+}
+
+impl Iterator for Scan {
+    type Item = ScanEntry;
+
+    fn next(&mut self) -> Option<ScanEntry> {
+        self.results.recv().ok()
+    }
+}
+
+/// Walks `root` recursively and probes every file whose detected format passes
+/// `ScanOptions::formats`. Files that are not recognised as images are skipped.
+///
+/// Traversal and probing run on background threads; they stop early if the returned
+/// iterator is dropped.
+pub fn scan<P: AsRef<Path>>(root: P, options: &ScanOptions) -> Scan {
+    let workers = match options.workers {
+        0 => thread::available_parallelism().map_or(4, |n| n.get()),
+        n => n,
+    };
+    let (path_tx, path_rx) = mpsc::sync_channel::<PathBuf>(workers * 4);
+    let (result_tx, result_rx) = mpsc::channel();
+
+    let root = root.as_ref().to_path_buf();
+    let walker_tx = result_tx.clone();
+    thread::spawn(move || {
+        let _ = walk(&root, &path_tx, &walker_tx);
+    });
+
+    let paths = Arc::new(Mutex::new(path_rx));
+    for _ in 0..workers {
+        let paths = Arc::clone(&paths);
+        let results = result_tx.clone();
+        let options = options.clone();
+        thread::spawn(move || loop {
+            let path = match paths.lock().map(|paths| paths.recv()) {
+                Ok(Ok(path)) => path,
+                _ => break,
+            };
+            if let Some(result) = probe(&path, &options) {
+                if results.send(ScanEntry { path, result }).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    Scan { results: result_rx }
+}
+
+/// Sends every file under `dir` to the workers. Returns `Err` once the scan was abandoned.
+fn walk(
+    dir: &Path,
+    paths: &mpsc::SyncSender<PathBuf>,
+    results: &Sender<ScanEntry>,
+) -> std::result::Result<(), ()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            let entry = ScanEntry {
+                path: dir.to_path_buf(),
+                result: Err(err.into()),
+            };
+            return results.send(entry).map_err(|_| ());
+        }
+    };
+    for entry in entries {
+        // `file_type` does not follow symlinks, so linked directories cannot cause cycles.
+        let (path, is_dir) = match entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))) {
+            Ok((path, file_type)) => (path, file_type.is_dir()),
+            Err(err) => {
+                let entry = ScanEntry {
+                    path: dir.to_path_buf(),
+                    result: Err(err.into()),
+                };
+                results.send(entry).map_err(|_| ())?;
+                continue;
+            }
+        };
+        if is_dir {
+            walk(&path, paths, results)?;
+        } else if path.is_file() {
+            paths.send(path).map_err(|_| ())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns `None` for files that are skipped by the format filter.
+fn probe(path: &Path, options: &ScanOptions) -> Option<Result<Image>> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => return Some(Err(err.into())),
+    };
+    let mut magic = Vec::with_capacity(SNIFF_LEN);
+    if let Err(err) = (&mut file).take(SNIFF_LEN as u64).read_to_end(&mut magic) {
+        return Some(Err(err.into()));
+    }
+    let len = Format::sniff_len(&magic);
+    if let Err(err) = (&mut file).take((len - magic.len()) as u64).read_to_end(&mut magic) {
+        return Some(Err(err.into()));
+    }
+    let format = Format::detect(&magic)?;
+    if let Some(formats) = &options.formats {
+        if !formats.contains(&format) {
+            return None;
+        }
+    }
+
+    let result = file.rewind().map_err(Into::into).and_then(|_| match &options.probe {
+        Some(probe) => Image::probe_reader(&mut file, probe),
+        None => Image::from_reader_with(&mut file, &options.parse),
+    });
+    Some(result.map(|mut image| {
+        image.check_ext(&path.to_string_lossy());
+        image
+    }))
+}
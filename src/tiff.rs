@@ -1,24 +1,42 @@
+use crate::error::Context;
+use crate::format::Format;
+use crate::length::Length;
 use crate::ImageReader;
 use binrw::BinRead;
-use std::io::{BufRead, Cursor, Seek};
+use std::collections::HashSet;
+use std::io::{BufRead, Cursor, Seek, SeekFrom};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tiff {
     pub width: u32,
     pub height: u32,
-    pub x_resolution: u32,
-    pub y_resolution: u32,
+    /// Pixels per `resolution_unit`; 0 when the tag is absent.
+    pub x_resolution: f32,
+    pub y_resolution: f32,
     pub resolution_unit: u32,
+    /// EXIF orientation (1-8), 1 when the tag is absent.
+    pub orientation: u16,
 }
 
+/// Classic TIFF header (version 42) or BigTIFF header (version 43).
 #[derive(BinRead, Debug)]
 pub struct TiffHeader {
     pub byte_order: ByteOrder,
     #[br(is_little = (byte_order == ByteOrder::Little))]
     pub version: u16,
+    /// BigTIFF only: size of offsets in bytes, always 8.
+    #[br(is_little = (byte_order == ByteOrder::Little), if(version == BIG_TIFF))]
+    pub offset_size: u16,
+    #[br(is_little = (byte_order == ByteOrder::Little), if(version == BIG_TIFF))]
+    pub reserved: u16,
     #[br(is_little = (byte_order == ByteOrder::Little))]
-    pub ifd0_offset: u32,
+    #[br(args(version == BIG_TIFF), parse_with = read_offset)]
+    pub ifd0_offset: u64,
 }
+
+pub const BIG_TIFF: u16 = 43;
+
 #[derive(BinRead, Debug, PartialEq)]
 pub enum ByteOrder {
     #[br(magic(0x4D4Du16))]
@@ -28,30 +46,96 @@ pub enum ByteOrder {
 }
 
 #[derive(BinRead, Debug)]
+#[br(import(big_tiff: bool))]
 pub struct Ifd {
-    pub entry_count: u16,
-    #[br(count = entry_count)]
+    #[br(args(big_tiff), parse_with = read_count)]
+    pub entry_count: u64,
+    #[br(count = entry_count, args { inner: (big_tiff,) })]
     pub data: Vec<IfdEntry>,
-    pub next_offset: u32,
+    #[br(args(big_tiff), parse_with = read_offset)]
+    pub next_offset: u64,
 }
 
 #[derive(BinRead, Debug)]
+#[br(import(big_tiff: bool))]
 pub struct IfdEntry {
     pub tag: TagType,
     pub data_type: EntryType,
-    pub data_count: u32,
-    #[br(if(data_count == 1 && (data_type == EntryType::Short || data_type == EntryType::Long)))]
-    pub data_value: [u16; 2],
-    #[br(if(data_count != 1 || !(data_type == EntryType::Short || data_type == EntryType::Long)))]
-    pub data_offset: u32,
+    #[br(args(big_tiff), parse_with = read_offset)]
+    pub data_count: u64,
+    /// The value itself when it fits in the field (4 bytes, or 8 in BigTIFF), otherwise the
+    /// offset of the value.
+    #[br(args(big_tiff), parse_with = read_offset)]
+    pub data_offset: u64,
+    #[br(calc = big_tiff)]
+    pub big_tiff: bool,
+}
+
+/// Reads a 4-byte offset, or an 8-byte one in BigTIFF.
+#[binrw::parser(reader, endian)]
+fn read_offset(big_tiff: bool) -> binrw::BinResult<u64> {
+    if big_tiff {
+        u64::read_options(reader, endian, ())
+    } else {
+        Ok(u32::read_options(reader, endian, ())? as u64)
+    }
+}
+
+/// Reads a 2-byte IFD entry count, or an 8-byte one in BigTIFF.
+#[binrw::parser(reader, endian)]
+fn read_count(big_tiff: bool) -> binrw::BinResult<u64> {
+    if big_tiff {
+        u64::read_options(reader, endian, ())
+    } else {
+        Ok(u16::read_options(reader, endian, ())? as u64)
+    }
 }
 
 impl IfdEntry {
-    pub fn read_value(&self) -> u32 {
-        match self.data_type {
-            EntryType::Short | EntryType::Long => self.data_value[0] as u32,
-            _ => self.data_offset,
+    /// Decodes the first SHORT, LONG or LONG8 value stored inline in the entry.
+    pub fn read_value(&self, byte_order: &ByteOrder) -> u64 {
+        let size = match self.data_type {
+            EntryType::Short => 16,
+            EntryType::Long => 32,
+            _ => return self.data_offset,
+        };
+        let field = if self.big_tiff { 64 } else { 32 };
+        match byte_order {
+            // Values are left-aligned in the field, so big-endian ones sit in the high bits.
+            ByteOrder::Big => self.data_offset >> (field - size),
+            ByteOrder::Little => self.data_offset & ((1 << size) - 1),
+        }
+    }
+
+    /// Decodes the first RATIONAL (or SHORT/LONG) value, following the value offset unless
+    /// it is stored inline.
+    pub fn read_rational<R: BufRead + Seek>(
+        &self,
+        reader: &mut R,
+        byte_order: &ByteOrder,
+    ) -> crate::Result<f32> {
+        if self.data_type != EntryType::Rational {
+            return Ok(self.read_value(byte_order) as f32);
+        }
+        let (numerator, denominator) = if self.big_tiff {
+            // A single RATIONAL fits in the 8-byte BigTIFF field.
+            let (high, low) = ((self.data_offset >> 32) as u32, self.data_offset as u32);
+            match byte_order {
+                ByteOrder::Big => (high, low),
+                ByteOrder::Little => (low, high),
+            }
+        } else {
+            reader.seek(SeekFrom::Start(self.data_offset))?;
+            if *byte_order == ByteOrder::Little {
+                <(u32, u32)>::read_le(reader).context(Format::Tiff, reader)?
+            } else {
+                <(u32, u32)>::read_be(reader).context(Format::Tiff, reader)?
+            }
+        };
+        if denominator == 0 {
+            return Ok(0.0);
         }
+        Ok(numerator as f32 / denominator as f32)
     }
 }
 
@@ -63,6 +147,8 @@ pub enum EntryType {
     Long,
     #[br(magic(5u16))]
     Rational,
+    #[br(magic(16u16))]
+    Long8,
 
     Other(u16),
 }
@@ -79,49 +165,109 @@ pub enum TagType {
     YResolution,
     #[br(magic(0x0128u16))]
     ResolutionUnit,
+    #[br(magic(0x0112u16))]
+    Orientation,
     Other(u16),
 }
 
-fn _dpi(unit: u32, resolution: u32) -> u32 {
-    if unit == 1 {
-        72
-    } else {
-        let upi = if unit == 2 { 1.0 } else { 2.54 };
-        (resolution as f32 * upi).round() as u32
+/// Converts a resolution in pixels per `unit` to dots per inch.
+fn _dpi(unit: u32, resolution: f32) -> f32 {
+    match unit {
+        // Unit 1 means no absolute unit, only an aspect ratio.
+        1 => 72.0,
+        3 if resolution > 0.0 => resolution * 2.54,
+        // Inch is the default when the tag is absent.
+        _ if resolution > 0.0 => resolution,
+        _ => 72.0,
     }
 }
 
 impl Tiff {
+    /// Describes the first page (IFD0).
     pub fn new<R: BufRead + Seek>(reader: &mut R) -> crate::Result<Self> {
-        let header = TiffHeader::read_le(reader)?;
-        let mut ifd: Ifd;
-        if header.byte_order == ByteOrder::Little {
-            reader
-                .seek(std::io::SeekFrom::Start(header.ifd0_offset as u64))?;
-            ifd = Ifd::read_le(reader)?;
-        } else {
-            reader
-                .seek(std::io::SeekFrom::Start(header.ifd0_offset as u64))?;
-            ifd = Ifd::read_be(reader)?;
+        let header = TiffHeader::read_le(reader).context(Format::Tiff, reader)?;
+        let (tiff, _) = Self::read_ifd(reader, &header, header.ifd0_offset)?;
+        Ok(tiff)
+    }
+
+    /// Walks the whole IFD chain and describes every page, in file order.
+    pub fn pages<R: BufRead + Seek>(reader: &mut R) -> crate::Result<Vec<Self>> {
+        let header = TiffHeader::read_le(reader).context(Format::Tiff, reader)?;
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        let mut offset = header.ifd0_offset;
+        while offset != 0 {
+            if !visited.insert(offset) {
+                return Err(crate::Error::CorruptChunk {
+                    format: Format::Tiff,
+                    offset,
+                    reason: "IFD chain loops back to an earlier IFD".to_string(),
+                });
+            }
+            let (page, next) = Self::read_ifd(reader, &header, offset)?;
+            pages.push(page);
+            offset = next;
         }
+        Ok(pages)
+    }
+
+    /// Reads the IFD at `offset`, returning the page it describes and the next IFD offset.
+    fn read_ifd<R: BufRead + Seek>(
+        reader: &mut R,
+        header: &TiffHeader,
+        offset: u64,
+    ) -> crate::Result<(Self, u64)> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let big_tiff = header.version == BIG_TIFF;
+        let byte_order = &header.byte_order;
+        let ifd = if *byte_order == ByteOrder::Little {
+            Ifd::read_le_args(reader, (big_tiff,)).context(Format::Tiff, reader)?
+        } else {
+            Ifd::read_be_args(reader, (big_tiff,)).context(Format::Tiff, reader)?
+        };
 
         let mut tiff = Tiff {
             width: 0,
             height: 0,
-            x_resolution: 0,
-            y_resolution: 0,
+            x_resolution: 0.0,
+            y_resolution: 0.0,
             resolution_unit: 0,
+            orientation: 1,
         };
-        ifd.data.iter().for_each(|x| match x.tag {
-            TagType::ImageWidth => tiff.width = x.read_value(),
-            TagType::ImageLength => tiff.height = x.read_value(),
-            TagType::XResolution => tiff.x_resolution = x.read_value(),
-            TagType::YResolution => tiff.y_resolution = x.read_value(),
-            TagType::ResolutionUnit => tiff.resolution_unit = x.read_value(),
-            _ => {}
-        });
+        let (count_size, entry_size) = if big_tiff { (8, 20) } else { (2, 12) };
+        let dimension = |index: usize, x: &IfdEntry| {
+            let value = x.read_value(byte_order);
+            u32::try_from(value).map_err(|_| crate::Error::DimensionOverflow {
+                format: Format::Tiff,
+                offset: offset + count_size + index as u64 * entry_size,
+                value,
+            })
+        };
+        for (index, x) in ifd.data.iter().enumerate() {
+            match x.tag {
+                TagType::ImageWidth => tiff.width = dimension(index, x)?,
+                TagType::ImageLength => tiff.height = dimension(index, x)?,
+                TagType::XResolution => tiff.x_resolution = x.read_rational(reader, byte_order)?,
+                TagType::YResolution => tiff.y_resolution = x.read_rational(reader, byte_order)?,
+                TagType::ResolutionUnit => tiff.resolution_unit = x.read_value(byte_order) as u32,
+                TagType::Orientation => tiff.orientation = x.read_value(byte_order) as u16,
+                _ => {}
+            }
+        }
 
-        Ok(tiff)
+        Ok((tiff, ifd.next_offset))
+    }
+
+    /// Horizontal and vertical resolution in dots per inch, without rounding.
+    pub fn dpi(&self) -> (f32, f32) {
+        (
+            _dpi(self.resolution_unit, self.x_resolution),
+            _dpi(self.resolution_unit, self.y_resolution),
+        )
+    }
+
+    pub fn has_resolution(&self) -> bool {
+        self.x_resolution > 0.0 && self.y_resolution > 0.0
     }
 }
 
@@ -130,11 +276,165 @@ impl ImageReader for Tiff {
         (self.width, self.height)
     }
 
+    fn width(&self) -> Length {
+        Length::Inches(self.width as f64 / self.dpi().0 as f64)
+    }
+
+    fn height(&self) -> Length {
+        Length::Inches(self.height as f64 / self.dpi().1 as f64)
+    }
+
     fn x_dpi(&self) -> u32 {
-        _dpi(self.resolution_unit, self.x_resolution)
+        self.dpi().0.round() as u32
     }
 
     fn y_dpi(&self) -> u32 {
-        _dpi(self.resolution_unit, self.y_resolution)
+        self.dpi().1.round() as u32
+    }
+
+    fn orientation(&self) -> u16 {
+        self.orientation
+    }
+}
+
+const X_RESOLUTION: u16 = 0x011A;
+const Y_RESOLUTION: u16 = 0x011B;
+const RESOLUTION_UNIT: u16 = 0x0128;
+
+/// Returns a copy of `data` with the first page's `XResolution`/`YResolution` set to `x_dpi`
+/// by `y_dpi` and `ResolutionUnit` set to inches.
+///
+/// Existing tags are overwritten in place. When any of them is missing, IFD0 is rewritten
+/// with the tags added and appended to the end of the file; the old IFD is left unused.
+pub fn set_dpi(data: &[u8], x_dpi: u32, y_dpi: u32) -> crate::Result<Vec<u8>> {
+    for dpi in [x_dpi, y_dpi] {
+        if dpi == 0 {
+            return Err(crate::Error::InvalidResolution {
+                format: Format::Tiff,
+                dpi,
+            });
+        }
+    }
+    let reader = &mut Cursor::new(data);
+    let header = TiffHeader::read_le(reader).context(Format::Tiff, reader)?;
+    let big_tiff = header.version == BIG_TIFF;
+    let little = header.byte_order == ByteOrder::Little;
+    reader.seek(SeekFrom::Start(header.ifd0_offset))?;
+    let ifd = if little {
+        Ifd::read_le_args(reader, (big_tiff,)).context(Format::Tiff, reader)?
+    } else {
+        Ifd::read_be_args(reader, (big_tiff,)).context(Format::Tiff, reader)?
+    };
+
+    let u16_bytes = |v: u16| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32_bytes = |v: u32| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u64_bytes = |v: u64| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+    let rational = |dpi: u32| [u32_bytes(dpi), u32_bytes(1)].concat();
+
+    let (count_size, entry_size, value_at) = if big_tiff { (8, 20, 12) } else { (2, 12, 8) };
+    let entries_start = header.ifd0_offset as usize + count_size;
+    let tag = |entry: &IfdEntry| match entry.tag {
+        TagType::XResolution => X_RESOLUTION,
+        TagType::YResolution => Y_RESOLUTION,
+        TagType::ResolutionUnit => RESOLUTION_UNIT,
+        _ => 0,
+    };
+    let find = |wanted: u16, data_type: EntryType| {
+        ifd.data
+            .iter()
+            .position(|entry| tag(entry) == wanted && entry.data_type == data_type)
+    };
+
+    let mut out = data.to_vec();
+    if let (Some(x), Some(y), Some(unit)) = (
+        find(X_RESOLUTION, EntryType::Rational),
+        find(Y_RESOLUTION, EntryType::Rational),
+        find(RESOLUTION_UNIT, EntryType::Short),
+    ) {
+        for (index, dpi) in [(x, x_dpi), (y, y_dpi)] {
+            let at = if big_tiff {
+                // A single RATIONAL is stored inline in BigTIFF.
+                entries_start + index * entry_size + value_at
+            } else {
+                ifd.data[index].data_offset as usize
+            };
+            out.get_mut(at..at + 8)
+                .ok_or(crate::Error::Truncated {
+                    format: Format::Tiff,
+                    offset: data.len() as u64,
+                })?
+                .copy_from_slice(&rational(dpi));
+        }
+        // SHORT values are left-aligned, so they start the value field in either byte order.
+        let at = entries_start + unit * entry_size + value_at;
+        out[at..at + 2].copy_from_slice(&u16_bytes(2));
+        return Ok(out);
+    }
+
+    // Rebuild IFD0 at the end of the file, keeping every other entry as it is.
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let mut entries: Vec<(u16, Vec<u8>)> = ifd
+        .data
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !matches!(tag(entry), X_RESOLUTION | Y_RESOLUTION | RESOLUTION_UNIT))
+        .map(|(index, _)| {
+            let at = entries_start + index * entry_size;
+            let raw = &data[at..at + entry_size];
+            let tag = if little {
+                u16::from_le_bytes([raw[0], raw[1]])
+            } else {
+                u16::from_be_bytes([raw[0], raw[1]])
+            };
+            (tag, raw.to_vec())
+        })
+        .collect();
+    let new_entry = |tag: u16, data_type: u16, value: Vec<u8>| {
+        let mut raw = [u16_bytes(tag), u16_bytes(data_type)].concat();
+        if big_tiff {
+            raw.extend_from_slice(&u64_bytes(1));
+        } else {
+            raw.extend_from_slice(&u32_bytes(1));
+        }
+        raw.extend_from_slice(&value);
+        raw.resize(entry_size, 0);
+        (tag, raw)
+    };
+    for (tag, dpi) in [(X_RESOLUTION, x_dpi), (Y_RESOLUTION, y_dpi)] {
+        let value = if big_tiff {
+            rational(dpi)
+        } else {
+            let offset = out.len() as u32;
+            out.extend_from_slice(&rational(dpi));
+            u32_bytes(offset).to_vec()
+        };
+        entries.push(new_entry(tag, 5, value));
+    }
+    entries.push(new_entry(RESOLUTION_UNIT, 3, u16_bytes(2).to_vec()));
+    entries.sort_by_key(|(tag, _)| *tag);
+
+    let ifd_offset = out.len() as u64;
+    if big_tiff {
+        out.extend_from_slice(&u64_bytes(entries.len() as u64));
+    } else {
+        out.extend_from_slice(&u16_bytes(entries.len() as u16));
+    }
+    for (_, raw) in &entries {
+        out.extend_from_slice(raw);
+    }
+    if big_tiff {
+        out.extend_from_slice(&u64_bytes(ifd.next_offset));
+        out[8..16].copy_from_slice(&u64_bytes(ifd_offset));
+    } else {
+        let ifd_offset = u32::try_from(ifd_offset).map_err(|_| crate::Error::CorruptChunk {
+            format: Format::Tiff,
+            offset: 4,
+            reason: "file too large for a classic TIFF offset".to_string(),
+        })?;
+        out.extend_from_slice(&u32_bytes(ifd.next_offset as u32));
+        out[4..8].copy_from_slice(&u32_bytes(ifd_offset));
     }
+    Ok(out)
 }
@@ -0,0 +1,247 @@
+use crate::error::Context;
+use crate::format::Format;
+use crate::tiff::Tiff;
+use crate::{Error, ImageReader, Result};
+use binrw::helpers::until_eof;
+use binrw::{BinRead, PosValue};
+use std::io::{BufRead, Cursor, Seek};
+
+#[derive(Debug, BinRead)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[br(little)]
+#[br(magic(b"RIFF"))]
+pub struct Webp {
+    #[br(ignore)]
+    pub width: u32,
+    #[br(ignore)]
+    pub height: u32,
+    #[br(ignore)]
+    pub exif: Option<Tiff>,
+    pub riff_size: u32,
+    #[br(magic(b"WEBP"))]
+    #[br(parse_with = until_eof)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub enum Chunk {
+    #[br(magic(b"VP8 "))]
+    Vp8(Vp8Chunk),
+    #[br(magic(b"VP8L"))]
+    Vp8l(Vp8lChunk),
+    #[br(magic(b"VP8X"))]
+    Vp8x(Vp8xChunk),
+    #[br(magic(b"EXIF"))]
+    Exif(ExifChunk),
+    Other(OtherChunk),
+}
+
+/// Lossy bitstream; the key frame header carries 14-bit dimensions and a 2-bit scale.
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub struct Vp8Chunk {
+    #[br(assert(length >= 10, "VP8 chunk length {} is shorter than its header", length))]
+    pub length: u32,
+    pub frame_tag: [u8; 3],
+    #[br(magic(b"\x9D\x01\x2A"))]
+    pub width: u16,
+    #[br(pad_after = length as i64 + (length & 1) as i64 - 10)]
+    pub height: u16,
+}
+
+/// Lossless bitstream; 14-bit `width - 1` and `height - 1` are packed after the signature.
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub struct Vp8lChunk {
+    #[br(assert(length >= 5, "VP8L chunk length {} is shorter than its header", length))]
+    pub length: u32,
+    #[br(magic(0x2Fu8))]
+    #[br(pad_after = length as i64 + (length & 1) as i64 - 5)]
+    pub bits: u32,
+}
+
+/// Extended format header; the canvas size is stored as 24-bit `width - 1` and `height - 1`.
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub struct Vp8xChunk {
+    #[br(assert(length >= 10, "VP8X chunk length {} is shorter than its header", length))]
+    pub length: u32,
+    pub flags: u8,
+    pub reserved: [u8; 3],
+    pub canvas_width: [u8; 3],
+    #[br(pad_after = length as i64 + (length & 1) as i64 - 10)]
+    pub canvas_height: [u8; 3],
+}
+
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub struct ExifChunk {
+    pub length: u32,
+    #[br(count = length)]
+    #[br(pad_after = (length % 2) as i64)]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, BinRead)]
+#[br(little)]
+pub struct OtherChunk {
+    pub chunk_type: [u8; 4],
+    pub length: u32,
+    /// Position of the payload, which is skipped rather than read.
+    #[br(pad_after = length as i64 + (length & 1) as i64)]
+    pub data: PosValue<()>,
+}
+
+impl Vp8lChunk {
+    pub fn width(&self) -> u32 {
+        (self.bits & 0x3FFF) + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        ((self.bits >> 14) & 0x3FFF) + 1
+    }
+}
+
+impl Vp8xChunk {
+    pub fn width(&self) -> u32 {
+        _u24(self.canvas_width) + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        _u24(self.canvas_height) + 1
+    }
+}
+
+impl ExifChunk {
+    pub fn tiff(&self) -> Result<Tiff> {
+        // Some encoders keep the JPEG APP1 identifier in front of the TIFF header.
+        let data = self.data.strip_prefix(b"Exif\0\0").unwrap_or(&self.data);
+        Tiff::new(&mut Cursor::new(data))
+    }
+}
+
+impl Webp {
+    /// Fails with `Error::CorruptChunk` when no `VP8 `, `VP8L` or `VP8X` chunk gives the
+    /// canvas size.
+    pub fn new<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
+        let webp = Webp::read(reader).context(Format::Webp, reader)?;
+        webp.load()
+    }
+
+    /// Reads chunk by chunk and stops once the canvas size is known, or past a VP8X header
+    /// whose flags announce EXIF metadata, once the `EXIF` chunk has been read. Skipped
+    /// chunks are sought over, so the image data is never read.
+    pub fn probe<R: BufRead + Seek>(reader: &mut R) -> Result<Self> {
+        let signature = <[u8; 4]>::read(reader).context(Format::Webp, reader)?;
+        let riff_size = u32::read_le(reader).context(Format::Webp, reader)?;
+        let form = <[u8; 4]>::read(reader).context(Format::Webp, reader)?;
+        if &signature != b"RIFF" || &form != b"WEBP" {
+            return Err(Error::InvalidMagic {
+                format: Format::Webp,
+                offset: 0,
+            });
+        }
+
+        let mut chunks = Vec::new();
+        let mut has_size = false;
+        let mut wants_exif = false;
+        while !reader.fill_buf()?.is_empty() {
+            let chunk = Chunk::read(reader).context(Format::Webp, reader)?;
+            match &chunk {
+                Chunk::Vp8x(vp8x) => {
+                    has_size = true;
+                    // Bit 3 of the VP8X flags announces an EXIF chunk.
+                    wants_exif = vp8x.flags & 0x08 != 0;
+                }
+                Chunk::Vp8(_) | Chunk::Vp8l(_) => has_size = true,
+                Chunk::Exif(_) => wants_exif = false,
+                Chunk::Other(_) => {}
+            }
+            chunks.push(chunk);
+            if has_size && !wants_exif {
+                break;
+            }
+        }
+
+        Webp {
+            width: 0,
+            height: 0,
+            exif: None,
+            riff_size,
+            chunks,
+        }
+        .load()
+    }
+
+    fn load(mut self) -> Result<Self> {
+        let mut has_size = false;
+        for chunk in &self.chunks {
+            match chunk {
+                Chunk::Vp8x(vp8x) => {
+                    self.width = vp8x.width();
+                    self.height = vp8x.height();
+                    has_size = true;
+                }
+                Chunk::Vp8(vp8) if !has_size => {
+                    self.width = (vp8.width & 0x3FFF) as u32;
+                    self.height = (vp8.height & 0x3FFF) as u32;
+                    has_size = true;
+                }
+                Chunk::Vp8l(vp8l) if !has_size => {
+                    self.width = vp8l.width();
+                    self.height = vp8l.height();
+                    has_size = true;
+                }
+                // Damaged EXIF data leaves the default resolution in place.
+                Chunk::Exif(exif) => self.exif = exif.tiff().ok(),
+                _ => {}
+            }
+        }
+        if !has_size {
+            // Chunks that fail to parse as a bitstream header are read as `Other`.
+            return Err(Error::CorruptChunk {
+                format: Format::Webp,
+                offset: 12,
+                reason: "missing or invalid VP8, VP8L or VP8X chunk".to_string(),
+            });
+        }
+        Ok(self)
+    }
+}
+
+impl ImageReader for Webp {
+    fn dimension(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn x_dpi(&self) -> u32 {
+        match &self.exif {
+            Some(tiff) if tiff.has_resolution() => tiff.x_dpi(),
+            _ => 72,
+        }
+    }
+
+    fn y_dpi(&self) -> u32 {
+        match &self.exif {
+            Some(tiff) if tiff.has_resolution() => tiff.y_dpi(),
+            _ => 72,
+        }
+    }
+
+    fn orientation(&self) -> u16 {
+        self.exif.as_ref().map_or(1, |tiff| tiff.orientation)
+    }
+
+    fn is_animated(&self) -> bool {
+        // Bit 1 of the VP8X flags announces ANIM/ANMF chunks.
+        self.chunks
+            .iter()
+            .any(|chunk| matches!(chunk, Chunk::Vp8x(vp8x) if vp8x.flags & 0x02 != 0))
+    }
+}
+
+fn _u24(bytes: [u8; 3]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16
+}
@@ -1,13 +1,16 @@
-use imagesize::bmp::Bmp;
-use imagesize::{Image, ImageReader};
-use std::{fs, io};
-use std::io::{Cursor, Read, Seek};
-use binrw::{BinRead, BinReaderExt};
-use imagesize::gif::Gif;
-use imagesize::jpeg::{Jpeg, Segment};
-use imagesize::jpeg::Marker::APP1;
-use imagesize::png::{Chunk, Info, Png};
+use binrw::BinRead;
+use imagesize::format::Format;
+use imagesize::heif::{Heif, Mirror};
+use imagesize::jpeg::Jpeg;
+use imagesize::length::{Length, Rounding};
+use imagesize::png::Info;
+use imagesize::probe::{ParseOptions, ProbeOptions};
+use imagesize::scan::{scan, ScanEntry, ScanOptions};
 use imagesize::tiff::Tiff;
+use imagesize::png::Png;
+use imagesize::{Error, Image, ImageReader};
+use std::fs;
+use std::io::{Cursor, Seek, SeekFrom};
 
 #[test]
 fn test_bmp() {
@@ -38,4 +41,1253 @@ fn test_png() {
 fn test_jpeg() {
     let bmp = Image::from_file("tests/images/jpeg.jpg").unwrap();
     println!("{:?}", bmp);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_detect_format() {
+    for (path, format) in [
+        ("tests/images/bmp.bmp", Format::Bmp),
+        ("tests/images/gif.gif", Format::Gif),
+        ("tests/images/jpeg.jpg", Format::Jpeg),
+        ("tests/images/png.png", Format::Png),
+        ("tests/images/tiff.tiff", Format::Tiff),
+        ("tests/images/webp-lossy.webp", Format::Webp),
+    ] {
+        let data = fs::read(path).unwrap();
+        assert_eq!(Format::detect(&data), Some(format));
+        let image = Image::from_file(path).unwrap();
+        assert_eq!(image.format, format);
+        assert!(!image.ext_mismatch);
+    }
+    assert_eq!(Format::detect(b"not an image"), None);
+    // "BM" alone is not enough: the DIB header size must be a known one.
+    assert_eq!(Format::detect(b"BM"), None);
+    assert_eq!(Format::detect(b"BMW is a car brand from Munich, Germany"), None);
+    assert!(matches!(
+        Image::from_bytes(b"BMW is a car brand from Munich, Germany"),
+        Err(Error::UnsupportedFormat)
+    ));
+}
+
+#[test]
+fn test_ext_mismatch() {
+    let dir = std::env::temp_dir().join("imagesize-ext-mismatch");
+    fs::create_dir_all(&dir).unwrap();
+    let renamed = dir.join("photo.jpg");
+    fs::copy("tests/images/png.png", &renamed).unwrap();
+    let image = Image::from_file(renamed.to_str().unwrap()).unwrap();
+    assert_eq!(image.format, Format::Png);
+    assert_eq!(image.content_type, "image/png");
+    assert!(image.ext_mismatch);
+
+    let bare = dir.join("upload");
+    fs::copy("tests/images/png.png", &bare).unwrap();
+    let image = Image::from_file(bare.to_str().unwrap()).unwrap();
+    assert_eq!(image.dimensions, (4, 3));
+    assert!(!image.ext_mismatch);
+}
+
+#[test]
+fn test_from_bytes_and_reader() {
+    let data = fs::read("tests/images/jpeg.jpg").unwrap();
+    let from_file = Image::from_file("tests/images/jpeg.jpg").unwrap();
+    let from_bytes = Image::from_bytes(&data).unwrap();
+    assert_eq!(from_bytes.crc32, from_file.crc32);
+    assert_eq!(from_bytes.dimensions, (16, 8));
+    assert_eq!((from_bytes.x_dpi, from_bytes.y_dpi), (300, 300));
+    assert_eq!(from_bytes.content_type, "image/jpeg");
+    assert_eq!(from_bytes.ext, "jpeg");
+
+    let mut prefixed = b"junk".to_vec();
+    prefixed.extend_from_slice(&data);
+    let mut reader = Cursor::new(prefixed);
+    reader.seek(SeekFrom::Start(4)).unwrap();
+    let from_reader = Image::from_reader(&mut reader).unwrap();
+    assert_eq!(from_reader.crc32, from_file.crc32);
+    assert_eq!(from_reader.dimensions, from_file.dimensions);
+}
+
+#[test]
+fn test_probe() {
+    for path in [
+        "tests/images/bmp.bmp",
+        "tests/images/gif.gif",
+        "tests/images/jpeg.jpg",
+        "tests/images/png.png",
+    ] {
+        let full = Image::from_file(path).unwrap();
+        let probed = Image::probe_file(path, &ProbeOptions::default()).unwrap();
+        assert_eq!(probed.crc32, 0);
+        assert_eq!(probed.dimensions, full.dimensions);
+        assert_eq!((probed.x_dpi, probed.y_dpi), (full.x_dpi, full.y_dpi));
+
+        let options = ProbeOptions {
+            crc32: true,
+            ..Default::default()
+        };
+        let probed = Image::probe_file(path, &options).unwrap();
+        assert_eq!(probed.crc32, full.crc32);
+    }
+}
+
+#[test]
+fn test_probe_stops_before_image_data() {
+    // Pad the file with a large IDAT; probing must stop at the first IDAT header.
+    let mut data = fs::read("tests/images/png.png").unwrap();
+    let idat_start = data.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+    let iend = data.split_off(data.len() - 12);
+    data.extend_from_slice(&(1_000_000u32).to_be_bytes());
+    data.extend_from_slice(b"IDAT");
+    data.extend(std::iter::repeat_n(0u8, 1_000_000 + 4));
+    data.extend_from_slice(&iend);
+
+    let mut reader = Cursor::new(b"prefix".iter().chain(&data).copied().collect::<Vec<_>>());
+    reader.seek(SeekFrom::Start(6)).unwrap();
+    let options = ProbeOptions {
+        max_bytes: Some(idat_start as u64 + 8),
+        crc32: false,
+        strict: false,
+    };
+    let image = Image::probe_reader(&mut reader, &options).unwrap();
+    assert_eq!(image.dimensions, (4, 3));
+    assert_eq!(image.x_dpi, 96);
+
+    let options = ProbeOptions {
+        max_bytes: Some(20),
+        crc32: false,
+        strict: false,
+    };
+    reader.seek(SeekFrom::Start(6)).unwrap();
+    assert!(matches!(
+        Image::probe_reader(&mut reader, &options),
+        Err(Error::LimitExceeded { limit: 20 })
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_probe_async() {
+    for path in [
+        "tests/images/bmp.bmp",
+        "tests/images/gif.gif",
+        "tests/images/jpeg.jpg",
+        "tests/images/png.png",
+    ] {
+        let full = Image::from_file(path).unwrap();
+        let mut file = tokio::fs::File::open(path).await.unwrap();
+        let options = ProbeOptions {
+            crc32: true,
+            ..Default::default()
+        };
+        let probed = Image::probe_async(&mut file, &options).await.unwrap();
+        assert_eq!(probed.crc32, full.crc32);
+        assert_eq!(probed.dimensions, full.dimensions);
+        assert_eq!((probed.x_dpi, probed.y_dpi), (full.x_dpi, full.y_dpi));
+        assert_eq!(probed.content_type, full.content_type);
+    }
+
+    let mut damaged = fs::read("tests/images/png.png").unwrap();
+    let at = damaged.windows(4).position(|w| w == b"pHYs").unwrap();
+    damaged[at + 5] ^= 1;
+    let options = ProbeOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        Image::probe_async(&mut Cursor::new(&damaged), &options).await,
+        Err(Error::ChecksumMismatch { chunk, .. }) if chunk == "pHYs"
+    ));
+}
+
+/// Async reader that counts the bytes read through it.
+#[cfg(feature = "tokio")]
+struct CountingReader {
+    inner: Cursor<Vec<u8>>,
+    read: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for CountingReader {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = std::pin::Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.read += buf.filled().len() - before;
+        poll
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncSeek for CountingReader {
+    fn start_seek(mut self: std::pin::Pin<&mut Self>, pos: SeekFrom) -> std::io::Result<()> {
+        std::pin::Pin::new(&mut self.inner).start_seek(pos)
+    }
+
+    fn poll_complete(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<u64>> {
+        std::pin::Pin::new(&mut self.inner).poll_complete(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_probe_async_seeks_to_metadata() {
+    // Move the IFD behind 1 MiB of padding at the end of the file.
+    let original = fs::read("tests/images/tiff.tiff").unwrap();
+    let ifd = original[8..8 + 2 + 5 * 12 + 4].to_vec();
+    let mut data = original.clone();
+    data.extend(std::iter::repeat_n(0u8, 1 << 20));
+    let ifd_offset = data.len() as u32;
+    data.extend_from_slice(&ifd);
+    data[4..8].copy_from_slice(&ifd_offset.to_le_bytes());
+
+    let full = Image::from_bytes(&data).unwrap();
+    let mut reader = CountingReader {
+        inner: Cursor::new(data),
+        read: 0,
+    };
+    let probed = Image::probe_async(&mut reader, &ProbeOptions::default()).await.unwrap();
+    assert_eq!(probed.dimensions, full.dimensions);
+    assert_eq!((probed.x_dpi, probed.y_dpi), (full.x_dpi, full.y_dpi));
+    assert!(reader.read < 64 * 1024, "read {} bytes", reader.read);
+
+    let options = ProbeOptions {
+        max_bytes: Some(1 << 20),
+        crc32: false,
+        strict: false,
+    };
+    reader.inner.set_position(0);
+    assert!(matches!(
+        Image::probe_async(&mut reader, &options).await,
+        Err(Error::LimitExceeded { limit: 1048576 })
+    ));
+}
+
+#[test]
+fn test_webp() {
+    let lossy = Image::from_file("tests/images/webp-lossy.webp").unwrap();
+    assert_eq!(lossy.format, Format::Webp);
+    assert_eq!(lossy.content_type, "image/webp");
+    assert_eq!(lossy.ext, "webp");
+    assert_eq!(lossy.dimensions, (30, 20));
+
+    let lossless = Image::from_file("tests/images/webp-lossless.webp").unwrap();
+    assert_eq!(lossless.dimensions, (7, 5));
+
+    let extended = Image::from_file("tests/images/webp-extended.webp").unwrap();
+    assert_eq!(extended.dimensions, (640, 480));
+
+    // A damaged EXIF chunk is ignored.
+    let mut data = fs::read("tests/images/webp-extended.webp").unwrap();
+    let exif = data.windows(4).position(|w| w == b"EXIF").unwrap();
+    data[exif + 8..exif + 12].copy_from_slice(b"junk");
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!(image.dimensions, (640, 480));
+    assert_eq!((image.x_dpi, image.y_dpi), (72, 72));
+}
+
+#[test]
+fn test_heif() {
+    let heic = Image::from_file("tests/images/heic.heic").unwrap();
+    assert_eq!(heic.format, Format::Heic);
+    assert_eq!(heic.content_type, "image/heic");
+    // Stored as 4032x3024, rotated by 90 degrees and mirrored top to bottom.
+    assert_eq!(heic.dimensions, (4032, 3024));
+    assert_eq!(heic.orientation, 5);
+    assert_eq!(heic.display_dimensions(), (3024, 4032));
+
+    let data = fs::read("tests/images/heic.heic").unwrap();
+    let heif = Heif::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!((heif.width, heif.height), (4032, 3024));
+    assert_eq!(heif.rotation, 90);
+    assert_eq!(heif.mirror, Some(Mirror::Horizontal));
+    assert_eq!(heif.orientation(), 5);
+    assert_eq!(heif.display_dimension(), (3024, 4032));
+
+    let avif = Image::from_file("tests/images/avif.avif").unwrap();
+    assert_eq!(avif.format, Format::Avif);
+    assert_eq!(avif.content_type, "image/avif");
+    assert_eq!(avif.ext, "avif");
+    assert_eq!(avif.dimensions, (1920, 1080));
+
+    let data = fs::read("tests/images/avif.avif").unwrap();
+    let heif = Heif::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!(heif.bits_per_channel, vec![8, 8, 8]);
+}
+
+/// `avif.avif` with its brands moved past the first `SNIFF_LEN` bytes of the file.
+fn _avif_with_long_ftyp() -> Vec<u8> {
+    let data = fs::read("tests/images/avif.avif").unwrap();
+    let mut ftyp = b"ftypmp42\0\0\0\0iso8mp41isomiso2avifmif1miaf".to_vec();
+    ftyp.splice(0..0, (ftyp.len() as u32 + 4).to_be_bytes());
+    ftyp.extend_from_slice(&data[24..]);
+    ftyp
+}
+
+#[test]
+fn test_heif_long_ftyp() {
+    let data = _avif_with_long_ftyp();
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!(image.format, Format::Avif);
+    assert_eq!(image.dimensions, (1920, 1080));
+
+    let probed = Image::probe_reader(&mut Cursor::new(&data), &ProbeOptions::default()).unwrap();
+    assert_eq!(probed.format, Format::Avif);
+    assert_eq!(probed.dimensions, (1920, 1080));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_heif_long_ftyp_async() {
+    let data = _avif_with_long_ftyp();
+    let probed = Image::probe_async(&mut Cursor::new(&data), &ProbeOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(probed.format, Format::Avif);
+    assert_eq!(probed.dimensions, (1920, 1080));
+}
+
+#[test]
+fn test_heif_box_size() {
+    let mut ftyp = b"\0\0\0\x14ftypheic\0\0\0\0mif1".to_vec();
+    ftyp.extend_from_slice(b"\0\0\0\x01free");
+    let mut large = ftyp.clone();
+    large.extend_from_slice(&u64::MAX.to_be_bytes());
+    // Used to panic with an overflow while computing the end of the box.
+    assert!(matches!(
+        Image::from_bytes(&large),
+        Err(Error::CorruptChunk { format: Format::Heif, offset: 20, .. })
+    ));
+
+    // Sizes smaller than the box header.
+    for size in [2u32, 7] {
+        let mut small = ftyp[..20].to_vec();
+        small.extend_from_slice(&size.to_be_bytes());
+        small.extend_from_slice(b"free\0\0\0\0");
+        assert!(matches!(
+            Image::from_bytes(&small),
+            Err(Error::CorruptChunk { format: Format::Heif, offset: 20, .. })
+        ));
+    }
+    let mut large = ftyp;
+    large.extend_from_slice(&8u64.to_be_bytes());
+    assert!(matches!(
+        Image::from_bytes(&large),
+        Err(Error::CorruptChunk { format: Format::Heif, offset: 20, .. })
+    ));
+}
+
+#[test]
+fn test_jpeg_orientation() {
+    let image = Image::from_file("tests/images/jpeg-exif.jpg").unwrap();
+    assert_eq!(image.orientation, 6);
+    assert_eq!(image.dimensions, (16, 8));
+    assert_eq!(image.display_dimensions(), (8, 16));
+
+    let data = fs::read("tests/images/jpeg-exif.jpg").unwrap();
+    let jpeg = Jpeg::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!(jpeg.orientation, 6);
+    assert_eq!(jpeg.display_dimension(), (8, 16));
+
+    let image = Image::from_file("tests/images/jpeg.jpg").unwrap();
+    assert_eq!(image.orientation, 1);
+    assert_eq!(image.display_dimensions(), image.dimensions);
+}
+
+#[test]
+fn test_jpeg_damaged_exif() {
+    // Point the EXIF XResolution past the end of the segment, and add the JFIF APP0 of
+    // `jpeg.jpg`.
+    let mut data = fs::read("tests/images/jpeg-exif.jpg").unwrap();
+    data[42..46].copy_from_slice(&0xFFFFu32.to_be_bytes());
+    let jfif = fs::read("tests/images/jpeg.jpg").unwrap()[2..20].to_vec();
+    data.splice(2..2, jfif);
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!(image.dimensions, (16, 8));
+    assert_eq!((image.x_dpi, image.y_dpi), (300, 300));
+    let probed = Image::probe_reader(&mut Cursor::new(&data), &ProbeOptions::default()).unwrap();
+    assert_eq!((probed.x_dpi, probed.y_dpi), (300, 300));
+}
+
+#[test]
+fn test_tiff_resolution() {
+    let data = fs::read("tests/images/tiff.tiff").unwrap();
+    let tiff = Tiff::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!((tiff.width, tiff.height), (6, 4));
+    assert!((tiff.x_resolution - 299.9).abs() < 1e-3);
+    assert!((tiff.dpi().1 - 299.9).abs() < 1e-3);
+    let image = Image::from_file("tests/images/tiff.tiff").unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (300, 300));
+
+    // Big-endian, SHORT width, LONG height and resolution in pixels per centimetre.
+    let image = Image::from_file("tests/images/tiff-be.tif").unwrap();
+    assert_eq!(image.dimensions, (6, 70000));
+    assert_eq!((image.x_dpi, image.y_dpi), (300, 300));
+
+    // Pixels per centimetre with a zero XResolution falls back to 72 dpi.
+    let mut data = fs::read("tests/images/tiff.tiff").unwrap();
+    data[0x42] = 3;
+    data[0x4a..0x4e].copy_from_slice(&0u32.to_le_bytes());
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (72, 762));
+
+    let image = Image::from_file("tests/images/jpeg-exif.jpg").unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (72, 72));
+    let image = Image::from_file("tests/images/webp-extended.webp").unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (300, 300));
+}
+
+#[test]
+fn test_tiff_pages() {
+    let data = fs::read("tests/images/tiff-multipage.tif").unwrap();
+    let pages = Tiff::pages(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].dimension(), (1728, 2200));
+    assert_eq!((pages[1].x_dpi(), pages[1].y_dpi()), (204, 98));
+    assert_eq!(pages[2].dimension(), (800, 600));
+
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!(image.dimensions, (1728, 2200));
+    assert_eq!((image.x_dpi, image.y_dpi), (204, 196));
+
+    // Point the last IFD back at the first one.
+    let mut looped = data.clone();
+    let last_next = looped.len() - 16 - 4;
+    looped[last_next..last_next + 4].copy_from_slice(&8u32.to_le_bytes());
+    assert!(matches!(
+        Tiff::pages(&mut Cursor::new(&looped)),
+        Err(Error::CorruptChunk { format: Format::Tiff, offset: 8, .. })
+    ));
+    assert!(Tiff::new(&mut Cursor::new(&looped)).is_ok());
+}
+
+#[test]
+fn test_bigtiff() {
+    for path in ["tests/images/bigtiff.tif", "tests/images/bigtiff-be.tif"] {
+        let image = Image::from_file(path).unwrap();
+        assert_eq!(image.format, Format::Tiff);
+        assert_eq!(image.dimensions, (100000, 3000));
+        assert_eq!((image.x_dpi, image.y_dpi), (300, 150));
+
+        let data = fs::read(path).unwrap();
+        let tiff = Tiff::new(&mut Cursor::new(data)).unwrap();
+        assert!((tiff.x_resolution - 299.9).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_errors() {
+    assert!(matches!(
+        Image::from_bytes(b"definitely not an image"),
+        Err(Error::UnsupportedFormat)
+    ));
+
+    let png = fs::read("tests/images/png.png").unwrap();
+    let err = Image::from_bytes(&png[..40]).unwrap_err();
+    assert!(matches!(err, Error::Truncated { format: Format::Png, .. }), "{err:?}");
+    assert!(err.to_string().starts_with("PNG: unexpected end of input"));
+
+    let jpeg = fs::read("tests/images/jpeg.jpg").unwrap();
+    assert!(matches!(
+        Png::new(&mut Cursor::new(&jpeg)),
+        Err(Error::InvalidMagic { format: Format::Png, offset: 0 })
+    ));
+
+    // BigTIFF LONG8 width of 2^32 in the first IFD entry.
+    let mut bigtiff = fs::read("tests/images/bigtiff.tif").unwrap();
+    bigtiff[36..44].copy_from_slice(&(1u64 << 32).to_le_bytes());
+    assert!(matches!(
+        Image::from_bytes(&bigtiff),
+        Err(Error::DimensionOverflow { format: Format::Tiff, offset: 24, value: 4294967296 })
+    ));
+
+    // An IHDR of the wrong length, and no IHDR at all.
+    let mut long_header = png.clone();
+    long_header[11] = 14;
+    long_header.insert(33, 0);
+    let mut no_header = png.clone();
+    no_header[12..16].copy_from_slice(b"iHDR");
+    for data in [long_header, no_header] {
+        assert!(matches!(
+            Png::new(&mut Cursor::new(&data)),
+            Err(Error::CorruptChunk { format: Format::Png, offset: 8, .. })
+        ));
+        assert!(matches!(
+            Png::probe(&mut Cursor::new(&data)),
+            Err(Error::CorruptChunk { format: Format::Png, offset: 8, .. })
+        ));
+    }
+
+    let err = Image::from_file("tests/images/missing.png").unwrap_err();
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(
+        source.downcast_ref::<std::io::Error>().unwrap().kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn test_cli() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_imagesize"))
+        .args(["--output", "csv", "--unit", "pt", "tests/images/png.png"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "path,width_px,height_px,x_dpi,y_dpi,width_pt,height_pt,content_type,crc32\n\
+         tests/images/png.png,4,3,96,96,3.00,2.25,image/png,0e2a7650\n"
+    );
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_imagesize"))
+        .args(["-o", "json", "tests/images/gif.gif", "Cargo.toml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("[{\"path\":\"tests/images/gif.gif\",\"width_px\":3,"));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Cargo.toml: "));
+
+    // Without JFIF or EXIF the resolution is unknown and so is the physical size.
+    let path = std::env::temp_dir().join("imagesize-no-jfif.jpg");
+    let mut data = fs::read("tests/images/jpeg.jpg").unwrap();
+    data.drain(2..20);
+    fs::write(&path, data).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_imagesize"))
+        .args(["-o", "json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"x_dpi\":0,\"y_dpi\":0,\"width\":null,\"height\":null,"));
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_imagesize"))
+        .args(["-o", "csv"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().nth(1).unwrap().ends_with(",16,8,0,0,,,image/jpeg,e29e96c7"));
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_imagesize"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let row: Vec<&str> = stdout.lines().nth(1).unwrap().split_whitespace().collect();
+    assert_eq!(row[1..], ["16", "8", "0", "0", "-", "-", "image/jpeg", "e29e96c7"]);
+}
+
+#[test]
+fn test_scan() {
+    let dir = std::env::temp_dir().join("imagesize-scan");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested/deeper")).unwrap();
+    fs::copy("tests/images/png.png", dir.join("a.png")).unwrap();
+    fs::copy("tests/images/jpeg.jpg", dir.join("nested/b.jpg")).unwrap();
+    fs::copy("tests/images/gif.gif", dir.join("nested/deeper/c.png")).unwrap();
+    fs::write(dir.join("nested/notes.txt"), "not an image").unwrap();
+
+    let mut entries: Vec<ScanEntry> = scan(&dir, &ScanOptions::default()).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let names: Vec<_> = entries.iter().map(|e| e.path.strip_prefix(&dir).unwrap().to_owned()).collect();
+    assert_eq!(
+        names,
+        [
+            std::path::Path::new("a.png"),
+            std::path::Path::new("nested/b.jpg"),
+            std::path::Path::new("nested/deeper/c.png"),
+        ]
+    );
+    let gif = entries[2].result.as_ref().unwrap();
+    assert_eq!(gif.format, Format::Gif);
+    assert!(gif.ext_mismatch);
+
+    let options = ScanOptions {
+        formats: Some(vec![Format::Jpeg, Format::Gif]),
+        workers: 2,
+        probe: Some(ProbeOptions::default()),
+        ..Default::default()
+    };
+    let mut formats: Vec<_> = scan(&dir, &options).map(|e| e.result.unwrap().format).collect();
+    formats.sort_by_key(|format| format.to_string());
+    assert_eq!(formats, [Format::Gif, Format::Jpeg]);
+
+    assert!(scan(dir.join("missing"), &ScanOptions::default()).next().unwrap().result.is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let image = Image::from_file("tests/images/png.png").unwrap();
+    let json = serde_json::to_value(&image).unwrap();
+    assert_eq!(json["format"], "png");
+    assert_eq!(json["width"]["unit"], "inches");
+    assert!((json["width"]["value"].as_f64().unwrap() - 4.0 / 96.0).abs() < 1e-6);
+
+    let decoded: Image = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.dimensions, image.dimensions);
+    assert_eq!(decoded.width.value(), image.width.value());
+    assert_eq!(decoded.format, Format::Png);
+
+    let mut file = fs::File::open("tests/images/jpeg-exif.jpg").unwrap();
+    let jpeg = Jpeg::new(&mut std::io::BufReader::new(&mut file)).unwrap();
+    let json = serde_json::to_value(&jpeg).unwrap();
+    assert_eq!(json["orientation"], 6);
+    assert!(json.get("segments").is_none());
+}
+
+#[test]
+fn test_length() {
+    let inch = Length::Inches(1.0);
+    assert_eq!(inch.to_emu(), 914400.0);
+    assert_eq!(inch.to_pt(), 72.0);
+    assert_eq!(inch.to_twips(), 1440.0);
+    assert!((inch.to_cm() - 2.54).abs() < 1e-12);
+    assert!((inch.to_mm() - 25.4).abs() < 1e-12);
+    assert_eq!(Length::Twips(1440.0).to_inches(), 1.0);
+    assert_eq!(inch.value(), 914400);
+    assert_eq!(inch.convert(Length::Pt(0.0)).amount(), 72.0);
+
+    assert_eq!(inch, Length::Pt(72.0));
+    assert_eq!(Length::Cm(1.0), Length::Mm(10.0));
+    assert!(Length::Cm(3.0) > inch);
+    assert!(Length::Twips(1439.0) < inch);
+
+    let sum = inch + Length::Pt(36.0);
+    assert!(matches!(sum, Length::Inches(v) if v == 1.5));
+    assert_eq!(inch - Length::Twips(720.0), Length::Inches(0.5));
+    assert_eq!(Length::Mm(10.0) * 2.5, Length::Mm(25.0));
+    assert_eq!(Length::Pt(10.0) / 4.0, Length::Pt(2.5));
+    assert_eq!(Length::Cm(5.0) / Length::Cm(2.0), 2.5);
+    assert_eq!(-Length::Pt(1.0), Length::Pt(-1.0));
+
+    let length = Length::Pt(10.7);
+    assert_eq!(length.round(Rounding::Truncate), Length::Pt(10.0));
+    assert_eq!(length.round(Rounding::Nearest), Length::Pt(11.0));
+    assert_eq!(Length::Pt(-10.5).round(Rounding::Floor), Length::Pt(-11.0));
+    assert_eq!(Length::Pt(10.1).round(Rounding::Ceil), Length::Pt(11.0));
+    assert_eq!(Length::Emu(1000.6).emu(Rounding::Nearest), 1001);
+    assert_eq!(Length::Emu(1000.6).emu(Rounding::default()), 1000);
+    assert_eq!(Length::Pt(0.3).twips(Rounding::Nearest), 6);
+}
+
+#[test]
+fn test_length_str() {
+    assert_eq!("5cm".parse::<Length>().unwrap(), Length::Cm(5.0));
+    assert!(matches!("2.5in".parse(), Ok(Length::Inches(v)) if v == 2.5));
+    assert!(matches!(" 12 pt ".parse(), Ok(Length::Pt(v)) if v == 12.0));
+    assert!(matches!("1440tw".parse(), Ok(Length::Twips(v)) if v == 1440.0));
+    assert!(matches!("914400emu".parse(), Ok(Length::Emu(v)) if v == 914400.0));
+    assert!(matches!("-3.5MM".parse(), Ok(Length::Mm(v)) if v == -3.5));
+    assert_eq!("300px@96dpi".parse::<Length>().unwrap(), Length::Inches(3.125));
+
+    for length in [
+        Length::Emu(914400.0),
+        Length::Inches(2.5),
+        Length::Cm(0.1),
+        Length::Mm(1.0 / 3.0),
+        Length::Pt(12.0),
+        Length::Twips(1440.0),
+        Length::Pc(1.5),
+        Length::Px { value: 300.0, dpi: 96.0 },
+        Length::Em { value: 2.0, font_pt: 10.5 },
+    ] {
+        let parsed: Length = length.to_string().parse().unwrap();
+        assert_eq!(parsed.to_string(), length.to_string());
+        assert_eq!(parsed, length);
+    }
+    assert_eq!(Length::Inches(2.5).to_string(), "2.5in");
+    assert_eq!(format!("{:.2}", Length::Cm(1.0 / 3.0)), "0.33cm");
+
+    use imagesize::length::ParseLengthError;
+    assert_eq!(
+        "5furlong".parse::<Length>(),
+        Err(ParseLengthError::UnknownUnit("furlong".to_string()))
+    );
+    assert_eq!(
+        "5".parse::<Length>(),
+        Err(ParseLengthError::MissingUnit("5".to_string()))
+    );
+    assert!(matches!("in".parse::<Length>(), Err(ParseLengthError::InvalidNumber(_))));
+    assert!(matches!("3px@dpi".parse::<Length>(), Err(ParseLengthError::InvalidDpi(_))));
+    assert!(matches!("3px@0dpi".parse::<Length>(), Err(ParseLengthError::InvalidDpi(_))));
+
+    let image = Image::from_file("tests/images/png.png").unwrap();
+    assert!(image.width < "1cm".parse().unwrap());
+}
+
+#[test]
+fn test_length_px() {
+    let px = Length::from_px(600.0, 300.0);
+    assert_eq!(px, Length::Inches(2.0));
+    assert_eq!(px.to_emu(), 1828800.0);
+    assert_eq!(px.to_pt(), 144.0);
+    assert_eq!(px.to_px(96.0), 192.0);
+    assert_eq!(Length::Cm(2.54).to_px(300.0).round(), 300.0);
+
+    assert_eq!(Length::css_px(96.0), Length::Inches(1.0));
+    assert_eq!(Length::Pc(6.0), Length::Inches(1.0));
+    assert_eq!(Length::Inches(1.0).to_pc(), 6.0);
+    assert_eq!(Length::Em { value: 2.0, font_pt: 12.0 }, Length::Pt(24.0));
+
+    // Arithmetic keeps the resolution of the left-hand side.
+    let sum = Length::css_px(96.0) + Length::Inches(1.0);
+    assert!(matches!(sum, Length::Px { value, dpi } if value == 192.0 && dpi == 96.0));
+    assert!(matches!(
+        Length::Pt(72.0).convert(Length::from_px(0.0, 600.0)),
+        Length::Px { value, .. } if value == 600.0
+    ));
+
+    assert_eq!("16px".parse::<Length>().unwrap(), Length::Pt(12.0));
+    assert_eq!("2em".parse::<Length>().unwrap(), Length::Pt(24.0));
+    assert_eq!("1.5em@10pt".parse::<Length>().unwrap(), Length::Pt(15.0));
+    assert_eq!("2pc".parse::<Length>().unwrap(), Length::Pt(24.0));
+    assert_eq!(Length::from_px(300.0, 96.0).to_string(), "300px@96dpi");
+
+    use imagesize::length::ParseLengthError;
+    assert!(matches!("1em@big".parse::<Length>(), Err(ParseLengthError::InvalidFontSize(_))));
+}
+
+#[test]
+fn test_fit() {
+    use imagesize::fit::FitMode;
+
+    let mut image = Image::from_file("tests/images/png.png").unwrap();
+    image.width = Length::Inches(8.0);
+    image.height = Length::Inches(6.0);
+    let (page_w, page_h) = (Length::Inches(6.5), Length::Inches(9.0));
+
+    let (w, h) = image.fit(page_w, page_h, FitMode::Contain);
+    assert_eq!((w, h), (Length::Inches(6.5), Length::Inches(4.875)));
+    let (w, h) = image.fit(page_w, page_h, FitMode::Cover);
+    assert_eq!((w, h), (Length::Inches(12.0), Length::Inches(9.0)));
+    let (w, h) = image.fit(page_w, page_h, FitMode::Width);
+    assert_eq!((w, h), (Length::Inches(6.5), Length::Inches(4.875)));
+    let (w, h) = image.fit(page_w, page_h, FitMode::Height);
+    assert_eq!((w, h), (Length::Inches(12.0), Length::Inches(9.0)));
+
+    // Results take the unit of the box.
+    let (w, h) = image.fit(Length::Twips(9360.0), Length::Pt(648.0), FitMode::ScaleDown);
+    assert!(matches!(w, Length::Twips(v) if v == 9360.0));
+    assert!(matches!(h, Length::Pt(v) if v == 351.0));
+
+    // Small images keep their natural size.
+    image.width = Length::Inches(2.0);
+    image.height = Length::Inches(1.0);
+    assert_eq!(
+        image.fit(page_w, page_h, FitMode::ScaleDown),
+        (Length::Inches(2.0), Length::Inches(1.0))
+    );
+    assert_eq!(
+        image.fit(page_w, page_h, FitMode::Contain),
+        (Length::Inches(6.5), Length::Inches(3.25))
+    );
+
+    // Rotated images are fitted as displayed.
+    image.orientation = 6;
+    assert_eq!(image.display_size(), (Length::Inches(1.0), Length::Inches(2.0)));
+    assert_eq!(
+        image.fit(page_w, page_h, FitMode::Contain),
+        (Length::Inches(4.5), Length::Inches(9.0))
+    );
+
+    // Without a resolution the natural size is taken at 72 dpi.
+    let mut data = fs::read("tests/images/jpeg.jpg").unwrap();
+    data.drain(2..20);
+    let image = Image::from_bytes(&data).unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (0, 0));
+    assert_eq!(image.display_size(), (Length::Inches(16.0 / 72.0), Length::Inches(8.0 / 72.0)));
+    assert_eq!(
+        image.fit(page_w, page_h, FitMode::Contain),
+        (Length::Inches(6.5), Length::Inches(3.25))
+    );
+}
+
+#[test]
+fn test_ooxml() {
+    use imagesize::ooxml::Picture;
+
+    // Parses a fragment inside a root that declares the prefixes its part would declare.
+    fn parse(fragment: &str) -> String {
+        let xml = format!(
+            "<root xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\" \
+             xmlns:p=\"http://schemas.openxmlformats.org/presentationml/2006/main\" \
+             xmlns:xdr=\"http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing\" \
+             xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\" \
+             xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">{}</root>",
+            fragment
+        );
+        roxmltree::Document::parse(&xml).unwrap();
+        xml
+    }
+
+    // 4x3 px at 96 dpi: 9525 EMU per pixel.
+    let image = Image::from_file("tests/images/png.png").unwrap();
+    let mut picture = Picture::new(&image, 7, "rId5", "Chart <1> & \"key\"");
+    picture.description = "Sales 'Q1'".to_string();
+    assert_eq!(picture.extent().unwrap(), (38100, 28575));
+
+    let inline = parse(&picture.docx_inline().unwrap());
+    assert!(inline.contains("<wp:inline "));
+    assert!(inline.contains("<wp:extent cx=\"38100\" cy=\"28575\"/>"));
+    assert!(inline.contains("<a:ext cx=\"38100\" cy=\"28575\"/>"));
+    assert!(inline.contains("<wp:docPr id=\"7\" name=\"Chart &lt;1&gt; &amp; &quot;key&quot;\" descr=\"Sales &apos;Q1&apos;\"/>"));
+    assert!(inline.contains("<a:blip r:embed=\"rId5\"/>"));
+
+    let anchor = parse(&picture.docx_anchor(Length::Inches(1.0), Length::Cm(1.0)).unwrap());
+    assert!(anchor.contains("<wp:positionH relativeFrom=\"column\"><wp:posOffset>914400</wp:posOffset>"));
+    assert!(anchor.contains("<wp:positionV relativeFrom=\"paragraph\"><wp:posOffset>360000</wp:posOffset>"));
+
+    let (width, height) = image.fit(Length::Inches(6.5), Length::Inches(9.0), Default::default());
+    picture.width = width;
+    picture.height = height;
+    let pptx = parse(&picture.pptx(Length::Pt(10.0), Length::Emu(0.0)).unwrap());
+    assert!(pptx.contains("<p:cNvPr id=\"7\" "));
+    assert!(pptx.contains("<a:off x=\"127000\" y=\"0\"/><a:ext cx=\"5943600\" cy=\"4457700\"/>"));
+
+    let xlsx = parse(&picture.xlsx(2, 10).unwrap());
+    assert!(xlsx.contains("<xdr:col>2</xdr:col>"));
+    assert!(xlsx.contains("<xdr:row>10</xdr:row>"));
+    assert!(xlsx.contains("<xdr:ext cx=\"5943600\" cy=\"4457700\"/>"));
+    assert!(xlsx.contains("<xdr:pic>"));
+
+    // Without a resolution the picture is sized at 72 dpi: 12700 EMU per pixel.
+    let mut data = fs::read("tests/images/jpeg.jpg").unwrap();
+    data.drain(2..20);
+    let image = Image::from_bytes(&data).unwrap();
+    let mut picture = Picture::new(&image, 1, "rId1", "photo");
+    assert_eq!(picture.extent().unwrap(), (203200, 101600));
+
+    picture.width = Length::Inches(f64::INFINITY);
+    assert!(matches!(picture.extent(), Err(Error::InvalidExtent(_))));
+    assert!(matches!(picture.docx_inline(), Err(Error::InvalidExtent(_))));
+    picture.width = Length::Inches(-1.0);
+    assert!(matches!(picture.xlsx(0, 0), Err(Error::InvalidExtent(_))));
+    picture.width = Length::Inches(1.0);
+    assert!(matches!(
+        picture.pptx(Length::Inches(f64::NAN), Length::Emu(0.0)),
+        Err(Error::InvalidExtent(_))
+    ));
+}
+
+#[test]
+fn test_set_dpi() {
+    for path in [
+        "tests/images/bmp.bmp",
+        "tests/images/jpeg.jpg",
+        "tests/images/jpeg-exif.jpg",
+        "tests/images/png.png",
+        "tests/images/tiff.tiff",
+        "tests/images/tiff-be.tif",
+        "tests/images/bigtiff.tif",
+        "tests/images/bigtiff-be.tif",
+    ] {
+        let data = fs::read(path).unwrap();
+        let before = Image::from_bytes(&data).unwrap();
+        let out = Image::set_dpi(&data, 300, 150).unwrap();
+        let after = Image::from_bytes(&out).unwrap();
+        assert_eq!((after.x_dpi, after.y_dpi), (300, 150), "{}", path);
+        assert_eq!(after.dimensions, before.dimensions, "{}", path);
+        assert_eq!(after.orientation, before.orientation, "{}", path);
+    }
+
+    // PNG without pHYs: the chunk is inserted after IHDR.
+    let data = fs::read("tests/images/png.png").unwrap();
+    let at = data.windows(4).position(|w| w == b"pHYs").unwrap() - 4;
+    let stripped = [&data[..at], &data[at + 21..]].concat();
+    assert_eq!(Image::from_bytes(&stripped).unwrap().x_dpi, 72);
+    let out = Image::set_dpi(&stripped, 600, 600).unwrap();
+    assert_eq!(out.len(), data.len());
+    assert_eq!(&out[33..41], b"\0\0\0\x09pHYs");
+    assert_eq!(Image::from_bytes(&out).unwrap().x_dpi, 600);
+
+    // JPEG without JFIF: an APP0 segment is inserted after SOI.
+    let data = fs::read("tests/images/jpeg.jpg").unwrap();
+    let length = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let stripped = [&data[..2], &data[4 + length..]].concat();
+    let out = Image::set_dpi(&stripped, 200, 100).unwrap();
+    assert_eq!(&out[2..4], &[0xFF, 0xE0]);
+    let image = Image::from_bytes(&out).unwrap();
+    assert_eq!((image.x_dpi, image.y_dpi), (200, 100));
+    assert_eq!(image.dimensions, (16, 8));
+
+    // TIFF without resolution tags: IFD0 is rewritten with the tags added.
+    let mut tiff = b"II*\0\x08\0\0\0\x02\0".to_vec();
+    tiff.extend_from_slice(&[0x00, 0x01, 3, 0, 1, 0, 0, 0, 40, 0, 0, 0]);
+    tiff.extend_from_slice(&[0x01, 0x01, 3, 0, 1, 0, 0, 0, 30, 0, 0, 0]);
+    tiff.extend_from_slice(&[0, 0, 0, 0]);
+    let image = Image::from_bytes(&tiff).unwrap();
+    assert_eq!((image.dimensions, image.x_dpi), ((40, 30), 72));
+    let out = Image::set_dpi(&tiff, 240, 120).unwrap();
+    let image = Image::from_bytes(&out).unwrap();
+    assert_eq!((image.dimensions, image.x_dpi, image.y_dpi), ((40, 30), 240, 120));
+
+    // Later pages stay reachable.
+    let data = fs::read("tests/images/tiff-multipage.tif").unwrap();
+    let out = Image::set_dpi(&data, 600, 600).unwrap();
+    let pages = Tiff::pages(&mut Cursor::new(&out)).unwrap();
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].dpi(), (600.0, 600.0));
+    assert_eq!(pages[2].dpi(), (300.0, 300.0));
+
+    let data = fs::read("tests/images/jpeg.jpg").unwrap();
+    assert!(matches!(
+        Image::set_dpi(&data, 70000, 72),
+        Err(Error::InvalidResolution { format: Format::Jpeg, dpi: 70000 })
+    ));
+    let data = fs::read("tests/images/gif.gif").unwrap();
+    assert!(matches!(Image::set_dpi(&data, 72, 72), Err(Error::UnsupportedFormat)));
+}
+
+#[test]
+fn test_png_strict() {
+    let data = fs::read("tests/images/png.png").unwrap();
+    Png::new_strict(&mut Cursor::new(&data)).unwrap();
+
+    // Flip a bit in the pHYs payload.
+    let at = data.windows(4).position(|w| w == b"pHYs").unwrap();
+    let mut damaged = data.clone();
+    damaged[at + 5] ^= 1;
+    assert!(Png::new(&mut Cursor::new(&damaged)).is_ok());
+    let err = Png::new_strict(&mut Cursor::new(&damaged)).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::ChecksumMismatch { format: Format::Png, chunk, offset }
+            if chunk == "pHYs" && *offset == at as u64 - 4
+    ));
+    assert_eq!(
+        err.to_string(),
+        format!("PNG: CRC mismatch in pHYs chunk at byte {}", at - 4)
+    );
+
+    // A damaged CRC field in the last chunk.
+    let mut damaged = data.clone();
+    let len = damaged.len();
+    damaged[len - 1] ^= 0xFF;
+    assert!(matches!(
+        Png::new_strict(&mut Cursor::new(&damaged)),
+        Err(Error::ChecksumMismatch { chunk, .. }) if chunk == "IEND"
+    ));
+}
+
+#[test]
+fn test_image_strict() {
+    let data = fs::read("tests/images/png.png").unwrap();
+    let at = data.windows(4).position(|w| w == b"pHYs").unwrap();
+    let mut damaged = data.clone();
+    damaged[at + 5] ^= 1;
+    let is_mismatch = |result: Result<Image, Error>| {
+        matches!(result, Err(Error::ChecksumMismatch { chunk, .. }) if chunk == "pHYs")
+    };
+
+    let strict = ParseOptions { strict: true };
+    Image::from_bytes_with(&data, &strict).unwrap();
+    assert!(Image::from_bytes(&damaged).is_ok());
+    assert!(is_mismatch(Image::from_bytes_with(&damaged, &strict)));
+    assert!(is_mismatch(Image::from_reader_with(&mut Cursor::new(&damaged), &strict)));
+
+    let mut options = ProbeOptions::default();
+    assert!(Image::probe_reader(&mut Cursor::new(&damaged), &options).is_ok());
+    options.strict = true;
+    Image::probe_reader(&mut Cursor::new(&data), &options).unwrap();
+    assert!(is_mismatch(Image::probe_reader(&mut Cursor::new(&damaged), &options)));
+    // The whole file has to fit in the byte budget.
+    options.max_bytes = Some(at as u64 + 16);
+    assert!(matches!(
+        Image::probe_reader(&mut Cursor::new(&data), &options),
+        Err(Error::LimitExceeded { .. })
+    ));
+
+    let dir = std::env::temp_dir().join("imagesize-strict");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("damaged.png");
+    fs::write(&path, &damaged).unwrap();
+    assert!(is_mismatch(Image::from_file_with(path.to_str().unwrap(), &strict)));
+    let options = ScanOptions {
+        parse: strict,
+        ..Default::default()
+    };
+    let entry = scan(&dir, &options).next().unwrap();
+    assert!(is_mismatch(entry.result));
+    let options = ScanOptions {
+        probe: Some(ProbeOptions { strict: true, ..Default::default() }),
+        ..Default::default()
+    };
+    let entry = scan(&dir, &options).next().unwrap();
+    assert!(is_mismatch(entry.result));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_png_header() {
+    use imagesize::png::{ColorType, CompressionMethod, FilterMethod, InterlaceMethod};
+
+    for probe in [false, true] {
+        let read = |path: &str| {
+            let mut reader = std::io::BufReader::new(fs::File::open(path).unwrap());
+            let png = if probe {
+                Png::probe(&mut reader)
+            } else {
+                Png::new(&mut reader)
+            };
+            png.unwrap()
+        };
+
+        let png = read("tests/images/png.png");
+        let info = png.info.as_ref().unwrap();
+        assert_eq!(info.bit_depth, 8);
+        assert_eq!(info.color_type, ColorType::Rgb);
+        assert_eq!(info.compression, CompressionMethod::Deflate);
+        assert_eq!(info.filter, FilterMethod::Adaptive);
+        assert_eq!(info.interlace, InterlaceMethod::None);
+        assert!(!png.has_alpha());
+
+        let png = read("tests/images/png-rgba.png");
+        let info = png.info.as_ref().unwrap();
+        assert_eq!((info.bit_depth, info.color_type), (16, ColorType::Rgba));
+        assert!(!info.transparency);
+        assert!(png.has_alpha());
+
+        let png = read("tests/images/png-trns.png");
+        let info = png.info.as_ref().unwrap();
+        assert_eq!((info.bit_depth, info.color_type), (4, ColorType::Indexed));
+        assert_eq!(info.interlace, InterlaceMethod::Adam7);
+        assert!(info.transparency);
+        assert!(png.has_alpha());
+    }
+}
+
+#[test]
+fn test_png_text() {
+    use imagesize::png::Text;
+
+    let mut reader = std::io::BufReader::new(fs::File::open("tests/images/png-text.png").unwrap());
+    let png = Png::new(&mut reader).unwrap();
+    let entries: Vec<_> = png
+        .text
+        .iter()
+        .map(|t| (t.keyword.as_str(), t.value.as_str(), t.compressed))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("Title", "Café menu", false),
+            ("Comment", "drawn by handdrawn by handdrawn by hand", true),
+            ("Author", "Jürgen Müller", true),
+            ("Source", "https://example.com/a?b=1", false),
+        ]
+    );
+    assert_eq!(
+        png.text[2],
+        Text {
+            keyword: "Author".to_string(),
+            value: "Jürgen Müller".to_string(),
+            compressed: true,
+            language: Some("de".to_string()),
+            translated_keyword: Some("Autor".to_string()),
+        }
+    );
+    assert_eq!(png.text[0].language, None);
+    assert_eq!(png.text[3].language.as_deref(), Some(""));
+
+    reader.rewind().unwrap();
+    assert!(Png::probe(&mut reader).unwrap().text.is_empty());
+}
+
+#[test]
+fn test_apng() {
+    use imagesize::png::{BlendOp, DisposeOp};
+    use std::time::Duration;
+
+    let mut reader = std::io::BufReader::new(fs::File::open("tests/images/apng.png").unwrap());
+    let png = Png::new(&mut reader).unwrap();
+    let animation = png.animation.as_ref().unwrap();
+    assert_eq!((animation.num_frames, animation.num_plays), (2, 0));
+    assert_eq!(animation.frames.len(), 2);
+
+    let first = &animation.frames[0];
+    assert!(first.is_default_image);
+    assert_eq!((first.width, first.height), (2, 2));
+    assert_eq!(first.delay(), Duration::from_millis(100));
+    assert_eq!((first.dispose_op, first.blend_op), (DisposeOp::None, BlendOp::Source));
+
+    let second = &animation.frames[1];
+    assert!(!second.is_default_image);
+    assert_eq!((second.x_offset, second.y_offset, second.width), (1, 1, 1));
+    assert_eq!(second.delay(), Duration::from_millis(500));
+    assert_eq!((second.dispose_op, second.blend_op), (DisposeOp::Background, BlendOp::Over));
+    assert_eq!(animation.duration(), Duration::from_millis(600));
+
+    reader.rewind().unwrap();
+    let probed = Png::probe(&mut reader).unwrap();
+    assert_eq!(probed.animation.unwrap().num_frames, 2);
+
+    let image = Image::from_file("tests/images/apng.png").unwrap();
+    assert!(image.animated);
+    assert!(Image::probe_file("tests/images/apng.png", &ProbeOptions::default()).unwrap().animated);
+    assert!(!Image::from_file("tests/images/png.png").unwrap().animated);
+
+    let image = Image::from_file("tests/images/webp-animated.webp").unwrap();
+    assert!(image.animated);
+    assert_eq!(image.dimensions, (100, 50));
+    assert!(!Image::from_file("tests/images/webp-extended.webp").unwrap().animated);
+}
+
+#[test]
+fn test_png_color() {
+    use imagesize::png::{Chromaticities, Cicp, RenderingIntent};
+
+    let mut reader = std::io::BufReader::new(fs::File::open("tests/images/png-color.png").unwrap());
+    for probe in [false, true] {
+        reader.rewind().unwrap();
+        let png = if probe {
+            Png::probe(&mut reader).unwrap()
+        } else {
+            Png::new(&mut reader).unwrap()
+        };
+        let color = &png.color;
+        let profile = color.icc_profile.as_ref().unwrap();
+        assert_eq!(profile.name, "Display P3");
+        assert_eq!(profile.data.len(), 512);
+        assert_eq!(&profile.data[..4], &[0, 1, 2, 3]);
+        assert_eq!(color.srgb_intent, Some(RenderingIntent::RelativeColorimetric));
+        assert_eq!(color.gamma, Some(0.45455));
+        assert_eq!(
+            color.chromaticities,
+            Some(Chromaticities {
+                white: (0.3127, 0.329),
+                red: (0.64, 0.33),
+                green: (0.3, 0.6),
+                blue: (0.15, 0.06),
+            })
+        );
+        assert_eq!(
+            color.cicp,
+            Some(Cicp {
+                colour_primaries: 9,
+                transfer_function: 16,
+                matrix_coefficients: 0,
+                video_full_range: true,
+            })
+        );
+    }
+
+    let mut reader = std::io::BufReader::new(fs::File::open("tests/images/png.png").unwrap());
+    let png = Png::new(&mut reader).unwrap();
+    assert!(png.color.icc_profile.is_none() && png.color.gamma.is_none() && png.color.cicp.is_none());
+}
+
+/// `png.png` with a chunk inserted after `IHDR`. The CRC is left at zero.
+fn _png_with_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut png = fs::read("tests/images/png.png").unwrap();
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&[0; 4]);
+    png.splice(33..33, chunk);
+    png
+}
+
+#[test]
+fn test_apng_chunk_length() {
+    // Chunks of the wrong length are skipped rather than misread.
+    for (chunk_type, len) in [(b"acTL", 12), (b"fcTL", 30), (b"fdAT", 2)] {
+        let data = _png_with_chunk(chunk_type, &vec![0; len]);
+        let png = Png::new(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(png.dimension(), (4, 3));
+        assert!(png.animation.is_none());
+        let png = Png::probe(&mut Cursor::new(&data)).unwrap();
+        assert!(png.animation.is_none());
+    }
+}
+
+#[test]
+fn test_png_color_chunk_length() {
+    // Chunks of the wrong length are skipped rather than misread.
+    for chunk_type in [b"sRGB", b"gAMA", b"cHRM", b"cICP"] {
+        let data = _png_with_chunk(chunk_type, &[0; 8]);
+        for probe in [false, true] {
+            let png = if probe {
+                Png::probe(&mut Cursor::new(&data)).unwrap()
+            } else {
+                Png::new(&mut Cursor::new(&data)).unwrap()
+            };
+            assert_eq!(png.dimension(), (4, 3));
+            let color = &png.color;
+            assert!(color.srgb_intent.is_none() && color.gamma.is_none());
+            assert!(color.chromaticities.is_none() && color.cicp.is_none());
+        }
+        let image = Image::probe_reader(&mut Cursor::new(&data), &ProbeOptions::default()).unwrap();
+        assert_eq!(image.dimensions, (4, 3));
+    }
+}
+
+#[test]
+fn test_webp_probe() {
+    for path in [
+        "tests/images/webp-lossy.webp",
+        "tests/images/webp-lossless.webp",
+        "tests/images/webp-extended.webp",
+        "tests/images/webp-animated.webp",
+    ] {
+        let full = Image::from_file(path).unwrap();
+        // Followed by 1 MiB of data the probe must not walk through.
+        let mut data = fs::read(path).unwrap();
+        data.extend_from_slice(b"XXXX");
+        data.extend_from_slice(&(1u32 << 20).to_le_bytes());
+        data.extend(std::iter::repeat_n(0u8, 1 << 20));
+        let options = ProbeOptions {
+            max_bytes: Some(64 * 1024),
+            crc32: false,
+            strict: false,
+        };
+        let probed = Image::probe_reader(&mut Cursor::new(&data), &options).unwrap();
+        assert_eq!(probed.dimensions, full.dimensions);
+        assert_eq!((probed.x_dpi, probed.y_dpi), (full.x_dpi, full.y_dpi));
+        assert_eq!(probed.animated, full.animated);
+    }
+}
+
+#[test]
+fn test_webp_chunk_length_overflow() {
+    let options = ProbeOptions::default();
+    for chunk_type in [&b"XXXX"[..], b"VP8 ", b"VP8L", b"VP8X"] {
+        let mut data = b"RIFF\0\0\0\0WEBP".to_vec();
+        data.extend_from_slice(chunk_type);
+        data.extend_from_slice(b"\xff\xff\xff\xff");
+        data.extend_from_slice(&[0; 16]);
+        // Used to panic with an overflow while computing the padding.
+        let _ = Image::from_bytes(&data);
+        let _ = Image::probe_reader(&mut Cursor::new(&data), &options);
+    }
+
+}
+
+#[test]
+fn test_webp_missing_bitstream() {
+    let lossy = fs::read("tests/images/webp-lossy.webp").unwrap();
+    let mut no_start_code = lossy.clone();
+    no_start_code[23..26].copy_from_slice(&[0; 3]);
+    // A VP8X chunk shorter than its fixed header is not mistaken for one.
+    let mut short_vp8x = b"RIFF\0\0\0\0WEBPVP8X\x04\0\0\0".to_vec();
+    short_vp8x.extend_from_slice(&[0x02, 0, 0, 0]);
+    let no_bitstream = b"RIFF\0\0\0\0WEBPXXXX\x02\0\0\0\0\0".to_vec();
+    for data in [no_start_code, short_vp8x, no_bitstream] {
+        assert!(matches!(
+            Image::from_bytes(&data),
+            Err(Error::CorruptChunk { format: Format::Webp, offset: 12, .. })
+        ));
+        assert!(matches!(
+            Image::probe_reader(&mut Cursor::new(&data), &ProbeOptions::default()),
+            Err(Error::CorruptChunk { format: Format::Webp, offset: 12, .. })
+        ));
+    }
+}